@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use smc_lib::key::SmcKey;
 
 #[derive(Parser)]
 #[command(
@@ -18,13 +19,18 @@ pub enum Commands {
     /// Read a single SMC key and display its value
     Read {
         #[arg(help = "Four-character SMC key name (e.g. TB0T, TCHP)")]
-        key: String,
+        key: SmcKey,
+        #[arg(
+            long,
+            help = "Diagnostic: also print the value decoded as little and big endian"
+        )]
+        both_orders: bool,
     },
 
     /// Write a value to a SMC key
     Write {
         #[arg(help = "Four-character SMC key name (e.g. TB0T, TCHP)")]
-        key: String,
+        key: SmcKey,
         #[arg(
             help = "Hexadecimal value to write (without `0x` prefix), for 0x031000, write 031000"
         )]
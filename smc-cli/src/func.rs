@@ -1,16 +1,28 @@
-use smc_lib::{
-    io::{IOService, err_str},
-    structs::SMC_BYTES_LEN,
-};
+#[cfg(not(target_os = "macos"))]
+use smc_lib::sysfs::SysfsSmc;
+use smc_lib::{io::IOService, key::SmcKey, structs::SMC_BYTES_LEN};
 use std::borrow::Cow;
 
+/// Opens the IOKit connection to the SMC.
+#[cfg(target_os = "macos")]
+fn open() -> Result<IOService, Cow<'static, str>> {
+    Ok(IOService::init().map_err(|e| e.to_string())?)
+}
+
+/// Opens the SMC through the Linux `applesmc` driver, which is read only.
+#[cfg(not(target_os = "macos"))]
+fn open() -> Result<IOService<SysfsSmc>, Cow<'static, str>> {
+    let smc = SysfsSmc::open().map_err(|e| format!("Can not open applesmc, error: {e}"))?;
+    Ok(IOService::with_transport(smc))
+}
+
 pub fn list() -> Result<(), Cow<'static, str>> {
-    let service = IOService::init()?;
-    let val_iter = service.values_iter().map_err(err_str)?;
+    let service = open()?;
+    let val_iter = service.values_iter().map_err(|e| e.to_string())?;
     for v in val_iter {
         match v {
             Ok(v) => {
-                println!("{v}")
+                println!("{}", v.annotated())
             }
             Err(e) => {
                 eprintln!("{e}");
@@ -20,21 +32,18 @@ pub fn list() -> Result<(), Cow<'static, str>> {
     Ok(())
 }
 
-pub fn read(key: &str) -> Result<(), Cow<'static, str>> {
-    let service = IOService::init()?;
-    let Ok(key) = key.as_bytes().try_into() else {
-        return Err("Invalid key!".into());
-    };
-    let val = service.read_key(key).map_err(err_str)?;
-    println!("{val}");
+pub fn read(key: SmcKey, both_orders: bool) -> Result<(), Cow<'static, str>> {
+    let service = open()?;
+    let val = service.read_key(key).map_err(|e| e.to_string())?;
+    println!("{}", val.annotated());
+    if both_orders && let Some((le, be)) = val.data_value_both() {
+        println!("le: {le}, be: {be}");
+    }
     Ok(())
 }
 
-pub fn write(key: &str, value: &str) -> Result<(), Cow<'static, str>> {
-    let service = IOService::init()?;
-    let Ok(key) = key.as_bytes().try_into() else {
-        return Err("Invalid key!".into());
-    };
+pub fn write(key: SmcKey, value: &str) -> Result<(), Cow<'static, str>> {
+    let service = open()?;
     if !value.is_ascii() {
         return Err("Value should be ascii!".into());
     }
@@ -53,6 +62,6 @@ pub fn write(key: &str, value: &str) -> Result<(), Cow<'static, str>> {
     }
     service
         .write_key(key, &value[..chunks.len()])
-        .map_err(err_str)?;
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
@@ -1,4 +1,3 @@
-#![cfg(target_os = "macos")]
 #![deny(clippy::unwrap_used)]
 
 pub mod command;
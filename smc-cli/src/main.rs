@@ -12,13 +12,13 @@ fn main() {
                 eprintln!("Error: {e}");
             }
         }
-        Commands::Read { key } => {
-            if let Err(e) = func::read(&key) {
+        Commands::Read { key, both_orders } => {
+            if let Err(e) = func::read(key, both_orders) {
                 eprintln!("Error: {e}");
             }
         }
         Commands::Write { key, value } => {
-            if let Err(e) = func::write(&key, &value) {
+            if let Err(e) = func::write(key, &value) {
                 eprintln!("Error: {e}");
             }
         }
@@ -0,0 +1,194 @@
+//! Battery, read from the `B0..` and `TB{n}T` keys.
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::io::IOService;
+//! use smc_lib::mock::MockSmc;
+//!
+//! let smc = IOService::with_transport(MockSmc::sample());
+//! let battery = smc.battery().unwrap().unwrap();
+//! assert_eq!(battery.cycle_count, Some(123));
+//! assert_eq!(battery.temperatures.len(), 1);
+//! ```
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::key::{INDEXED_KEY_LIMIT, SmcKey};
+use crate::value::SmcValue;
+
+/// Whether the battery is being charged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargingState {
+    Charging,
+    Discharging,
+    /// No current flows, e.g. the battery is full or charging is inhibited.
+    Idle,
+}
+
+impl std::fmt::Display for ChargingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChargingState::Charging => write!(f, "charging"),
+            ChargingState::Discharging => write!(f, "discharging"),
+            ChargingState::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+/// State of the battery, returned by [`IOService::battery`].
+///
+/// Keys that are missing on this Mac are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatteryInfo {
+    /// Charge cycles, `B0CT`.
+    pub cycle_count: Option<u32>,
+    /// Temperatures in °C of the `TB{n}T` sensors that exist, as `(n, °C)`
+    /// in the order of `n`, up to [`INDEXED_KEY_LIMIT`].
+    pub temperatures: Vec<(u8, f64)>,
+    /// Capacity when new in mAh, `B0DC`.
+    pub design_capacity: Option<u32>,
+    /// Capacity when fully charged in mAh, `B0FC`.
+    pub full_charge_capacity: Option<u32>,
+    /// Remaining capacity in mAh, `B0RM`.
+    pub remaining_capacity: Option<u32>,
+    /// Voltage in V, from `B0AV` in mV.
+    pub voltage: Option<f64>,
+    /// Current in A, negative while discharging, from `B0AC` in mA.
+    pub current: Option<f64>,
+    /// From the battery status bits `BSIn`, or on Macs without `BSIn` from
+    /// the sign of [`current`](Self::current).
+    pub charging: Option<ChargingState>,
+    /// Full charge capacity as a percentage of the design capacity.
+    pub health: Option<f64>,
+    /// Charge in %, `BRSC`, or the remaining capacity as a percentage of the
+    /// full charge capacity.
+    pub charge: Option<f64>,
+}
+
+impl<T: SmcTransport> IOService<T> {
+    /// Returns the state of the battery.
+    ///
+    /// Returns `Ok(None)` if `BNum` reports no battery, e.g. on a desktop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key exists but can not be read, missing keys
+    /// are `None` in [`BatteryInfo`].
+    pub fn battery(&self) -> Result<Option<BatteryInfo>, SmcError> {
+        if let Some(SmcValue::U8(0)) = self.read_optional(b"BNum")? {
+            return Ok(None);
+        }
+        let count = |key: &[u8; 4]| -> Result<Option<u32>, SmcError> {
+            Ok(self.read_number(key)?.map(|n| n.max(0.0) as u32))
+        };
+        let mut temperatures = Vec::new();
+        for n in 0..INDEXED_KEY_LIMIT {
+            let key = SmcKey::from([b'T', b'B', b'0' + n, b'T']);
+            if let Some(temp) = self.read_number(key)? {
+                temperatures.push((n, temp));
+            }
+        }
+        let design_capacity = count(b"B0DC")?;
+        let full_charge_capacity = count(b"B0FC")?;
+        let remaining_capacity = count(b"B0RM")?;
+        let current = self.read_number(b"B0AC")?.map(|ma| ma / 1000.0);
+        let charging = match self.read_optional(b"BSIn")? {
+            // bit 0 is set while charging, bit 1 while on AC power
+            Some(SmcValue::U8(bits)) => Some(if bits & 0x01 != 0 {
+                ChargingState::Charging
+            } else if bits & 0x02 != 0 {
+                ChargingState::Idle
+            } else {
+                ChargingState::Discharging
+            }),
+            _ => current.map(|a| {
+                if a > 0.0 {
+                    ChargingState::Charging
+                } else if a < 0.0 {
+                    ChargingState::Discharging
+                } else {
+                    ChargingState::Idle
+                }
+            }),
+        };
+        let percent = |part: Option<u32>, whole: Option<u32>| match (part, whole) {
+            (Some(part), Some(whole)) if whole > 0 => {
+                Some(f64::from(part) * 100.0 / f64::from(whole))
+            }
+            _ => None,
+        };
+        let charge = match self.read_number(b"BRSC")? {
+            Some(charge) => Some(charge),
+            None => percent(remaining_capacity, full_charge_capacity),
+        };
+        Ok(Some(BatteryInfo {
+            cycle_count: count(b"B0CT")?,
+            temperatures,
+            design_capacity,
+            full_charge_capacity,
+            remaining_capacity,
+            voltage: self.read_number(b"B0AV")?.map(|mv| mv / 1000.0),
+            current,
+            charging,
+            health: percent(full_charge_capacity, design_capacity),
+            charge,
+        }))
+    }
+}
+
+#[test]
+fn battery() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::SMC_ATTR_READ;
+
+    let r = SMC_ATTR_READ;
+    let mock = MockSmc::sample();
+    mock.insert(*b"BNum", MockKey::new(b"ui8 ", r, &[1]));
+    mock.insert(*b"TB2T", MockKey::new(b"flt ", r, &30.5f32.to_le_bytes()));
+    mock.insert(*b"B0DC", MockKey::new(b"ui16", r, &5000u16.to_le_bytes()));
+    mock.insert(*b"B0FC", MockKey::new(b"ui16", r, &4500u16.to_le_bytes()));
+    mock.insert(*b"B0RM", MockKey::new(b"ui16", r, &2250u16.to_le_bytes()));
+    mock.insert(*b"B0AV", MockKey::new(b"ui16", r, &12600u16.to_le_bytes()));
+    mock.insert(
+        *b"B0AC",
+        MockKey::new(b"si16", r, &(-1500i16).to_le_bytes()),
+    );
+    let smc = IOService::with_transport(mock);
+
+    let battery = smc.battery().expect("keys are readable");
+    let battery = battery.expect("BNum is 1");
+    assert_eq!(battery.cycle_count, Some(123));
+    assert_eq!(battery.temperatures, [(0, 25.8f32 as f64), (2, 30.5)]);
+    assert_eq!(battery.full_charge_capacity, Some(4500));
+    assert_eq!(battery.remaining_capacity, Some(2250));
+    assert_eq!(battery.voltage, Some(12.6));
+    assert_eq!(battery.current, Some(-1.5));
+    assert_eq!(battery.charging, Some(ChargingState::Discharging));
+    assert_eq!(battery.health, Some(90.0));
+    assert_eq!(battery.charge, Some(50.0));
+
+    // BSIn wins over the current, e.g. on AC with charging inhibited
+    let charging = |bits: u8| {
+        smc.transport()
+            .insert(*b"BSIn", MockKey::new(b"ui8 ", r, &[bits]));
+        smc.battery().map(|b| b.and_then(|b| b.charging))
+    };
+    assert_eq!(charging(0x02), Ok(Some(ChargingState::Idle)));
+    assert_eq!(charging(0x03), Ok(Some(ChargingState::Charging)));
+    assert_eq!(charging(0x00), Ok(Some(ChargingState::Discharging)));
+    smc.transport().remove(*b"BSIn");
+
+    smc.transport()
+        .insert(*b"BRSC", MockKey::new(b"ui16", r, &51u16.to_le_bytes()));
+    for key in [b"B0DC", b"B0AC"] {
+        smc.transport().remove(*key);
+    }
+    let battery = smc.battery().expect("keys are readable");
+    let battery = battery.expect("BNum is 1");
+    assert_eq!((battery.health, battery.charging), (None, None));
+    assert_eq!(battery.charge, Some(51.0));
+
+    smc.transport()
+        .insert(*b"BNum", MockKey::new(b"ui8 ", r, &[0]));
+    assert_eq!(smc.battery(), Ok(None));
+}
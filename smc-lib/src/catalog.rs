@@ -0,0 +1,376 @@
+//! Catalog of well known SMC keys.
+//!
+//! The meaning of a key is not stored in the SMC. This module maps known keys,
+//! and key patterns such as `TC{n}C` or `F{n}Ac`, to a description, a unit, a
+//! [`Category`], the usual data type and a typical range. Keys differ between
+//! models, so a key in the catalog may be missing or have another data type
+//! on a given Mac, see [AsahiLinux Docs](https://asahilinux.org/docs/hw/soc/smc)
+//! for more keys.
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::catalog::{self, Category, Unit};
+//!
+//! let sensor = catalog::lookup(b"F1Ac").unwrap();
+//! assert_eq!(sensor.description(), "fan 1 actual speed");
+//! assert_eq!(sensor.entry.unit, Some(Unit::Rpm));
+//! assert_eq!(sensor.entry.category, Category::Fan);
+//! assert_eq!(sensor.index, Some(1));
+//!
+//! for entry in catalog::by_category(Category::Battery) {
+//!     println!("{} {}", entry.pattern, entry.description);
+//! }
+//! ```
+
+use crate::data_type::{DataType, FixedPoint};
+use crate::key::SmcKey;
+use crate::structs::SMCVal;
+use std::{borrow::Cow, ops::RangeInclusive};
+
+/// Kind of quantity measured or controlled by a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Temperature,
+    Fan,
+    Power,
+    Voltage,
+    Current,
+    Battery,
+    System,
+}
+
+impl Category {
+    /// Every category, in display order.
+    pub const ALL: [Self; 7] = [
+        Self::Temperature,
+        Self::Fan,
+        Self::Power,
+        Self::Voltage,
+        Self::Current,
+        Self::Battery,
+        Self::System,
+    ];
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Temperature => "temperature",
+            Self::Fan => "fan",
+            Self::Power => "power",
+            Self::Voltage => "voltage",
+            Self::Current => "current",
+            Self::Battery => "battery",
+            Self::System => "system",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Physical unit of a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// `°C`
+    Celsius,
+    /// `rpm`
+    Rpm,
+    /// `W`
+    Watt,
+    /// `V`
+    Volt,
+    /// `A`
+    Ampere,
+    /// `mV`
+    Millivolt,
+    /// `mA`
+    Milliampere,
+    /// `mAh`
+    MilliampereHour,
+    /// `%`
+    Percent,
+}
+
+impl std::fmt::Display for Unit {
+    /// Writes the symbol of the unit.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Self::Celsius => "°C",
+            Self::Rpm => "rpm",
+            Self::Watt => "W",
+            Self::Volt => "V",
+            Self::Ampere => "A",
+            Self::Millivolt => "mV",
+            Self::Milliampere => "mA",
+            Self::MilliampereHour => "mAh",
+            Self::Percent => "%",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A key, or a family of keys, of the catalog.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatalogEntry {
+    /// The key, or a pattern where `{n}` stands for one digit, e.g. `F{n}Ac`.
+    pub pattern: &'static str,
+    /// What the key measures or controls, `{n}` is replaced by the digit.
+    pub description: &'static str,
+    /// `None` for counts, flags and other values without a unit.
+    pub unit: Option<Unit>,
+    pub category: Category,
+    /// The usual data type, Intel Macs often use fixed point types such as
+    /// `sp78` where Apple Silicon uses `flt `.
+    pub data_type: DataType,
+    /// Values usually seen on a working Mac, in [`unit`](Self::unit).
+    pub range: Option<RangeInclusive<f64>>,
+}
+
+impl CatalogEntry {
+    /// Returns `true` if the entry describes `key`.
+    pub fn matches(&self, key: impl Into<SmcKey>) -> bool {
+        self.match_key(key.into()).is_some()
+    }
+
+    /// Returns `Some(n)` if the key matches, `n` is the `{n}` digit.
+    fn match_key(&self, key: SmcKey) -> Option<Option<u8>> {
+        let key = key.as_bytes();
+        let Some((prefix, suffix)) = self.pattern.split_once("{n}") else {
+            return (self.pattern.as_bytes() == key).then_some(None);
+        };
+        let (prefix, suffix) = (prefix.as_bytes(), suffix.as_bytes());
+        if prefix.len() + 1 + suffix.len() != key.len()
+            || !key.starts_with(prefix)
+            || !key.ends_with(suffix)
+        {
+            return None;
+        }
+        let digit = key[prefix.len()];
+        digit.is_ascii_digit().then_some(Some(digit - b'0'))
+    }
+}
+
+/// A key found in the catalog, returned by [`lookup`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sensor {
+    pub key: SmcKey,
+    /// The `{n}` digit of the key, if the entry is a pattern.
+    pub index: Option<u8>,
+    pub entry: &'static CatalogEntry,
+}
+
+impl Sensor {
+    /// Returns the description, with `{n}` replaced by the index.
+    pub fn description(&self) -> Cow<'static, str> {
+        match self.index {
+            Some(n) => self.entry.description.replace("{n}", &n.to_string()).into(),
+            None => self.entry.description.into(),
+        }
+    }
+
+    /// Returns `true` if `value` is in the typical range, or if the range is
+    /// not known.
+    pub fn is_typical(&self, value: f64) -> bool {
+        self.entry.range.as_ref().is_none_or(|r| r.contains(&value))
+    }
+}
+
+/// Returns every entry of the catalog.
+pub fn entries() -> &'static [CatalogEntry] {
+    CATALOG
+}
+
+/// Returns the entries of `category`.
+pub fn by_category(category: Category) -> impl Iterator<Item = &'static CatalogEntry> {
+    CATALOG.iter().filter(move |e| e.category == category)
+}
+
+/// Looks `key` up, exact keys take precedence over patterns.
+pub fn lookup(key: impl Into<SmcKey>) -> Option<Sensor> {
+    let key = key.into();
+    let exact = CATALOG
+        .iter()
+        .find(|e| !e.pattern.contains("{n}") && e.matches(key));
+    if let Some(entry) = exact {
+        return Some(Sensor {
+            key,
+            index: None,
+            entry,
+        });
+    }
+    CATALOG.iter().find_map(|entry| {
+        let index = entry.match_key(key)?;
+        Some(Sensor { key, index, entry })
+    })
+}
+
+impl SMCVal {
+    /// Returns the catalog entry of the key, see [`lookup`].
+    pub fn sensor(&self) -> Option<Sensor> {
+        lookup(self.key)
+    }
+
+    /// Returns a `Display` adapter that writes the value followed by its
+    /// unit and description, for keys found in the catalog.
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// let val = smc.read_key(b"F0Ac").unwrap();
+    /// assert!(val.annotated().to_string().ends_with("value: 1200 rpm (fan 0 actual speed)"));
+    /// ```
+    pub fn annotated(&self) -> Annotated<'_> {
+        Annotated { val: self }
+    }
+}
+
+/// Writes a [`SMCVal`] with its unit and description, see
+/// [`SMCVal::annotated`].
+pub struct Annotated<'a> {
+    val: &'a SMCVal,
+}
+
+impl std::fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.val)?;
+        let Some(sensor) = self.val.sensor() else {
+            return Ok(());
+        };
+        if let Some(unit) = sensor.entry.unit
+            && self.val.data_value().is_some_and(|v| v.as_f64().is_some())
+        {
+            write!(f, " {}", unit)?;
+        }
+        write!(f, " ({})", sensor.description())
+    }
+}
+
+const SP78: DataType = DataType::Sp(FixedPoint::new(7, 8).expect("sp78 has 15 bits"));
+
+macro_rules! entry {
+    ($pattern:literal, $description:literal, $unit:expr, $category:ident, $data_type:expr, $range:expr) => {
+        CatalogEntry {
+            pattern: $pattern,
+            description: $description,
+            unit: $unit,
+            category: Category::$category,
+            data_type: $data_type,
+            range: $range,
+        }
+    };
+}
+
+const C: Option<Unit> = Some(Unit::Celsius);
+const RPM: Option<Unit> = Some(Unit::Rpm);
+const W: Option<Unit> = Some(Unit::Watt);
+const V: Option<Unit> = Some(Unit::Volt);
+const A: Option<Unit> = Some(Unit::Ampere);
+const TEMP: Option<RangeInclusive<f64>> = Some(0.0..=110.0);
+
+#[rustfmt::skip]
+static CATALOG: &[CatalogEntry] = &[
+    // temperature
+    entry!("TA{n}P", "ambient {n} temperature", C, Temperature, SP78, Some(0.0..=60.0)),
+    entry!("TB{n}T", "battery sensor {n} temperature", C, Temperature, DataType::Flt, Some(0.0..=60.0)),
+    entry!("TC{n}C", "CPU core {n} temperature", C, Temperature, SP78, TEMP),
+    entry!("TC{n}D", "CPU die {n} temperature", C, Temperature, SP78, TEMP),
+    entry!("TC{n}E", "CPU {n} virtual temperature", C, Temperature, SP78, TEMP),
+    entry!("TC{n}F", "CPU {n} filtered temperature", C, Temperature, SP78, TEMP),
+    entry!("TC{n}P", "CPU {n} proximity temperature", C, Temperature, SP78, TEMP),
+    entry!("TCHP", "charger proximity temperature", C, Temperature, DataType::Flt, TEMP),
+    entry!("TG{n}D", "GPU die {n} temperature", C, Temperature, SP78, TEMP),
+    entry!("TG{n}P", "GPU {n} proximity temperature", C, Temperature, SP78, TEMP),
+    entry!("TM{n}P", "memory {n} proximity temperature", C, Temperature, SP78, TEMP),
+    entry!("TW{n}P", "wireless module {n} proximity temperature", C, Temperature, SP78, TEMP),
+    entry!("Ts{n}P", "palm rest {n} temperature", C, Temperature, SP78, Some(0.0..=50.0)),
+    // fan
+    entry!("FNum", "number of fans", None, Fan, DataType::Ui8, Some(0.0..=4.0)),
+    entry!("FS! ", "forced fans, one bit per fan", None, Fan, DataType::Ui16, None),
+    entry!("F{n}Ac", "fan {n} actual speed", RPM, Fan, DataType::Flt, Some(0.0..=7000.0)),
+    entry!("F{n}ID", "fan {n} descriptor", None, Fan, DataType::Fds, None),
+    entry!("F{n}Md", "fan {n} mode, 0 automatic, 1 manual", None, Fan, DataType::Ui8, Some(0.0..=1.0)),
+    entry!("F{n}Mn", "fan {n} minimum speed", RPM, Fan, DataType::Flt, Some(0.0..=7000.0)),
+    entry!("F{n}Mx", "fan {n} maximum speed", RPM, Fan, DataType::Flt, Some(0.0..=7000.0)),
+    entry!("F{n}Tg", "fan {n} target speed", RPM, Fan, DataType::Flt, Some(0.0..=7000.0)),
+    // power
+    entry!("PCPC", "CPU package cores power", W, Power, DataType::Flt, Some(0.0..=150.0)),
+    entry!("PCPG", "CPU package GPU power", W, Power, DataType::Flt, Some(0.0..=150.0)),
+    entry!("PDTR", "DC in total power", W, Power, DataType::Flt, Some(0.0..=250.0)),
+    entry!("PPBR", "battery power", W, Power, DataType::Flt, Some(0.0..=150.0)),
+    entry!("PSTR", "system total power", W, Power, DataType::Flt, Some(0.0..=250.0)),
+    // voltage
+    entry!("VC{n}C", "CPU core {n} voltage", V, Voltage, DataType::Flt, Some(0.0..=2.0)),
+    entry!("VD0R", "DC in voltage", V, Voltage, DataType::Flt, Some(0.0..=30.0)),
+    entry!("VP0R", "12V rail voltage", V, Voltage, DataType::Flt, Some(0.0..=15.0)),
+    // current
+    entry!("IC{n}C", "CPU core {n} current", A, Current, DataType::Flt, Some(0.0..=100.0)),
+    entry!("ID0R", "DC in current", A, Current, DataType::Flt, Some(0.0..=10.0)),
+    // battery
+    entry!("BNum", "number of batteries", None, Battery, DataType::Ui8, Some(0.0..=2.0)),
+    entry!("B0AC", "battery current", Some(Unit::Milliampere), Battery, DataType::Si16, Some(-10000.0..=10000.0)),
+    entry!("B0AV", "battery voltage", Some(Unit::Millivolt), Battery, DataType::Ui16, Some(9000.0..=13500.0)),
+    entry!("B0CT", "battery cycle count", None, Battery, DataType::Ui16, Some(0.0..=3000.0)),
+    entry!("B0DC", "battery design capacity", Some(Unit::MilliampereHour), Battery, DataType::Ui16, Some(0.0..=10000.0)),
+    entry!("B0FC", "battery full charge capacity", Some(Unit::MilliampereHour), Battery, DataType::Ui16, Some(0.0..=10000.0)),
+    entry!("B0RM", "battery remaining capacity", Some(Unit::MilliampereHour), Battery, DataType::Ui16, Some(0.0..=10000.0)),
+    entry!("BRSC", "battery relative state of charge", Some(Unit::Percent), Battery, DataType::Ui16, Some(0.0..=100.0)),
+    entry!("BSIn", "battery status bits, charging and AC power", None, Battery, DataType::Ui8, None),
+    entry!("BCLM", "battery charge level maximum, Intel", Some(Unit::Percent), Battery, DataType::Ui8, Some(20.0..=100.0)),
+    entry!("CH0B", "charging inhibit, 0x02 stops charging, older Apple Silicon firmware", None, Battery, DataType::Hex, None),
+    entry!("CH0C", "undocumented, written with CH0B to inhibit charging", None, Battery, DataType::Hex, None),
+    entry!("CHTE", "charging inhibit, recent Apple Silicon firmware", None, Battery, DataType::Ui32, None),
+    entry!("CHWA", "limit charge to 80%, Apple Silicon", None, Battery, DataType::Ui8, Some(0.0..=1.0)),
+    entry!("CHLS", "charge limit status", None, Battery, DataType::Ui8, None),
+    // system
+    entry!("#KEY", "number of keys", None, System, DataType::Ui32, None),
+    entry!("ACLC", "MagSafe LED color", None, System, DataType::Ui8, Some(0.0..=4.0)),
+    entry!("MSLD", "lid closed", None, System, DataType::Ui8, Some(0.0..=1.0)),
+    entry!("REV ", "SMC firmware revision", None, System, DataType::Rev, None),
+    entry!("RGEN", "SMC generation", None, System, DataType::Ui8, None),
+    entry!("RPlt", "platform name", None, System, DataType::Ch8, None),
+];
+
+#[test]
+fn catalog() {
+    for entry in entries() {
+        let digits = entry.pattern.replace("{n}", "0");
+        assert_eq!(digits.len(), 4, "{} is not a key", entry.pattern);
+        assert_eq!(
+            entry.pattern.contains("{n}"),
+            entry.description.contains("{n}"),
+            "{}",
+            entry.pattern
+        );
+        let key: SmcKey = digits.parse().expect("patterns are ASCII");
+        let sensor = lookup(key).expect("every entry can be looked up");
+        assert_eq!(sensor.entry.pattern, entry.pattern, "{} is shadowed", key);
+    }
+    assert!(
+        Category::ALL
+            .iter()
+            .all(|c| by_category(*c).next().is_some())
+    );
+
+    let sensor = lookup(b"TC3C").expect("TC{n}C");
+    assert_eq!(sensor.index, Some(3));
+    assert_eq!(sensor.description(), "CPU core 3 temperature");
+    assert!(sensor.is_typical(50.0) && !sensor.is_typical(200.0));
+    assert_eq!(lookup(b"TCHP").map(|s| s.index), Some(None));
+    assert_eq!(lookup(b"TCXC"), None);
+    assert_eq!(
+        lookup(b"FNum").map(|s| s.entry.category),
+        Some(Category::Fan)
+    );
+
+    let smc = crate::io::IOService::with_transport(crate::mock::MockSmc::sample());
+    let val = smc.read_key(b"TB0T").expect("TB0T exists");
+    assert!(
+        val.annotated()
+            .to_string()
+            .ends_with(" °C (battery sensor 0 temperature)")
+    );
+    let val = smc.read_key(b"RPlt").expect("RPlt exists");
+    assert!(val.annotated().to_string().ends_with(" (platform name)"));
+}
@@ -0,0 +1,582 @@
+//! Battery charge limit.
+//!
+//! Macs expose different keys to limit the charge of the battery, see
+//! [`ChargeScheme`]. [`IOService::charge_scheme`] finds the one of this Mac
+//! in the key table, and a [`ChargeLimiter`] sets, reads and clears a limit
+//! with any of them. Writing needs root.
+//!
+//! With the [native](ChargeScheme::is_native) schemes the SMC stops charging
+//! by itself and keeps the limit. The other schemes can only turn charging
+//! on and off, so the limit is kept by the limiter: [`ChargeLimiter::run`]
+//! watches the charge and turns charging off at the limit, and on again
+//! once the charge dropped by [`ChargeLimit::hysteresis`]. The SMC keeps
+//! charging off after the limiter is gone, which a new limiter reports as
+//! [`LimitState::Inhibited`].
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::charge::{ChargeLimit, ChargeLimiter, ChargeScheme, LimitState};
+//! use smc_lib::io::IOService;
+//! use smc_lib::mock::{MockKey, MockSmc};
+//! use smc_lib::structs::{SMC_ATTR_READ, SMC_ATTR_WRITE};
+//!
+//! let mock = MockSmc::new();
+//! mock.insert(*b"BCLM", MockKey::new(b"ui8 ", SMC_ATTR_READ | SMC_ATTR_WRITE, &[100]));
+//! mock.set_privileged(true);
+//! let smc = IOService::with_transport(mock);
+//!
+//! let mut limiter = ChargeLimiter::new(&smc).unwrap();
+//! assert_eq!(limiter.scheme(), ChargeScheme::Bclm);
+//! limiter.set_limit(ChargeLimit::new(80)).unwrap();
+//! assert_eq!(limiter.limit().map(|l| l.percent), Some(80));
+//! limiter.clear_limit().unwrap();
+//! assert_eq!(limiter.state(), LimitState::Unlimited);
+//! ```
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::watch::{Clock, SystemClock, WatchEvent, Watcher};
+use std::{ops::ControlFlow, time::Duration};
+
+/// Keys used to limit the charge of the battery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargeScheme {
+    /// `BCLM`, the maximum charge level in %, on Intel Macs.
+    Bclm,
+    /// `CHWA`, limits the charge to 80%, on Apple Silicon with older
+    /// firmware.
+    Chwa,
+    /// `CHTE`, inhibits charging, on Apple Silicon with recent firmware.
+    Chte,
+    /// `CH0B` and `CH0C`, inhibit charging, on Apple Silicon with older
+    /// firmware.
+    Ch0b,
+}
+
+impl ChargeScheme {
+    /// Schemes in order of preference.
+    const ALL: [Self; 4] = [Self::Bclm, Self::Chwa, Self::Chte, Self::Ch0b];
+
+    /// Returns `true` if the SMC enforces the limit by itself, otherwise
+    /// [`ChargeLimiter::run`] has to keep it as the charge changes.
+    pub fn is_native(self) -> bool {
+        matches!(self, Self::Bclm | Self::Chwa)
+    }
+
+    /// Returns the limits supported by the scheme, in %.
+    pub fn range(self) -> std::ops::RangeInclusive<u8> {
+        match self {
+            Self::Bclm => 20..=100,
+            Self::Chwa => 80..=80,
+            Self::Chte | Self::Ch0b => 1..=100,
+        }
+    }
+
+    /// Returns the keys the scheme needs.
+    fn keys(self) -> &'static [&'static [u8; 4]] {
+        match self {
+            Self::Bclm => &[b"BCLM"],
+            Self::Chwa => &[b"CHWA"],
+            Self::Chte => &[b"CHTE"],
+            Self::Ch0b => &[b"CH0B", b"CH0C"],
+        }
+    }
+}
+
+impl std::fmt::Display for ChargeScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Bclm => "BCLM",
+            Self::Chwa => "CHWA",
+            Self::Chte => "CHTE",
+            Self::Ch0b => "CH0B/CH0C",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Errors returned when setting a charge limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeError {
+    /// This Mac has none of the keys of a [`ChargeScheme`].
+    Unsupported,
+    /// The charge of the battery can not be read, e.g. the Mac has no
+    /// battery.
+    NoBattery,
+    /// The requested limit is not supported by the scheme of this Mac, in %.
+    LimitOutOfRange { percent: u8, min: u8, max: u8 },
+    /// A key of the scheme can not be read or written.
+    Smc(SmcError),
+}
+
+impl From<SmcError> for ChargeError {
+    fn from(err: SmcError) -> Self {
+        ChargeError::Smc(err)
+    }
+}
+
+impl std::fmt::Display for ChargeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChargeError::Unsupported => write!(f, "this Mac has no charge limit keys"),
+            ChargeError::NoBattery => write!(f, "the battery charge can not be read"),
+            ChargeError::LimitOutOfRange { percent, min, max } => write!(
+                f,
+                "charge limit {}% is not supported, {}..={}%",
+                percent, min, max
+            ),
+            ChargeError::Smc(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ChargeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChargeError::Unsupported
+            | ChargeError::NoBattery
+            | ChargeError::LimitOutOfRange { .. } => None,
+            ChargeError::Smc(err) => Some(err),
+        }
+    }
+}
+
+/// A charge limit, in % of the full charge capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChargeLimit {
+    /// Charging stops at this charge.
+    pub percent: u8,
+    /// Charging resumes once the charge dropped by this much below
+    /// [`percent`](Self::percent). Only used by the schemes that are not
+    /// [native](ChargeScheme::is_native), the SMC has its own otherwise.
+    pub hysteresis: u8,
+}
+
+impl ChargeLimit {
+    /// Creates a limit with a hysteresis of 5%.
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent,
+            hysteresis: 5,
+        }
+    }
+
+    /// Sets the hysteresis.
+    pub fn with_hysteresis(mut self, hysteresis: u8) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+}
+
+/// The charge limit of a [`ChargeLimiter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LimitState {
+    /// No limit, the battery charges to 100%.
+    Unlimited,
+    /// The limit set in the SMC, or by [`ChargeLimiter::set_limit`].
+    Limited(ChargeLimit),
+    /// Charging is inhibited with a scheme that is not
+    /// [native](ChargeScheme::is_native). The SMC only stores that charging
+    /// is off, not the limit that turned it off, e.g. when it was set by
+    /// another limiter.
+    Inhibited,
+}
+
+impl<T: SmcTransport> IOService<T> {
+    /// Returns the charge limit scheme of this Mac, `None` if it has none.
+    pub fn charge_scheme(&self) -> Result<Option<ChargeScheme>, SmcError> {
+        for scheme in ChargeScheme::ALL {
+            if self.has_keys(scheme.keys())? {
+                return Ok(Some(scheme));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns `true` if charging is inhibited by a scheme that is not
+    /// [native](ChargeScheme::is_native), `false` for the native ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChargeError::Unsupported`] if this Mac has no charge limit
+    /// scheme, and the errors of [`IOService::read_key`].
+    pub fn charging_inhibited(&self) -> Result<bool, ChargeError> {
+        let inhibited = match self.required_charge_scheme()? {
+            ChargeScheme::Bclm | ChargeScheme::Chwa => false,
+            ChargeScheme::Chte => self
+                .read_key(b"CHTE")?
+                .valid_bytes()
+                .iter()
+                .any(|&b| b != 0),
+            ChargeScheme::Ch0b => self
+                .read_key(b"CH0B")?
+                .valid_bytes()
+                .iter()
+                .any(|&b| b != 0),
+        };
+        Ok(inhibited)
+    }
+
+    fn required_charge_scheme(&self) -> Result<ChargeScheme, ChargeError> {
+        self.charge_scheme()?.ok_or(ChargeError::Unsupported)
+    }
+
+    fn has_keys(&self, keys: &[&[u8; 4]]) -> Result<bool, SmcError> {
+        for key in keys {
+            match self.get_key_info(*key) {
+                Ok(_) => {}
+                Err(SmcError::KeyNotFound) => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn inhibit_charging(&self, scheme: ChargeScheme, inhibit: bool) -> Result<(), SmcError> {
+        match scheme {
+            ChargeScheme::Chte => self.write_value(b"CHTE", u32::from(inhibit)),
+            ChargeScheme::Ch0b => {
+                let value = if inhibit { 0x02 } else { 0x00 };
+                self.write_key(b"CH0B", &[value])?;
+                self.write_key(b"CH0C", &[value])
+            }
+            ChargeScheme::Bclm | ChargeScheme::Chwa => Ok(()),
+        }
+    }
+}
+
+/// Limits the charge of the battery with the scheme of this Mac.
+///
+/// The limiter holds the configured limit. With a
+/// [native](ChargeScheme::is_native) scheme the limit is written to the SMC,
+/// which keeps it after the limiter is dropped, and a new limiter reads it
+/// back. With the other schemes the limit is only kept while the limiter
+/// [runs](Self::run). Dropping the limiter leaves charging as it is, a new
+/// limiter reports charging that is still off as [`LimitState::Inhibited`]
+/// until the limit is set or [cleared](Self::clear_limit).
+///
+/// The time is taken from a [`Clock`], see [`with_clock`](Self::with_clock).
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(target_os = "macos")] {
+/// use smc_lib::charge::{ChargeLimit, ChargeLimiter};
+/// use smc_lib::io::IOService;
+/// use std::{ops::ControlFlow, time::Duration};
+///
+/// let smc = IOService::init().unwrap();
+/// let mut limiter = ChargeLimiter::new(&smc).unwrap();
+/// limiter.set_limit(ChargeLimit::new(80)).unwrap();
+/// limiter.run(Duration::from_secs(60), |result| {
+///     if let Err(err) = result {
+///         eprintln!("can not keep the charge limit: {}", err);
+///     }
+///     ControlFlow::Continue(())
+/// });
+/// # }
+/// ```
+pub struct ChargeLimiter<'a, T: SmcTransport, C = SystemClock> {
+    smc: &'a IOService<T>,
+    clock: C,
+    scheme: ChargeScheme,
+    state: LimitState,
+}
+
+impl<'a, T: SmcTransport> ChargeLimiter<'a, T> {
+    /// Creates a limiter for the scheme of this Mac.
+    ///
+    /// With a native scheme, the limit set in the SMC becomes the limit of
+    /// the limiter. With the other schemes, the state is
+    /// [`LimitState::Inhibited`] if charging is off.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChargeError::Unsupported`] if this Mac has no charge limit
+    /// scheme, and the errors of reading the limit.
+    pub fn new(smc: &'a IOService<T>) -> Result<Self, ChargeError> {
+        let scheme = smc.required_charge_scheme()?;
+        let state = match scheme {
+            ChargeScheme::Bclm => match smc.read_key(b"BCLM")?.valid_bytes() {
+                [percent] if *percent < 100 => LimitState::Limited(ChargeLimit::new(*percent)),
+                _ => LimitState::Unlimited,
+            },
+            ChargeScheme::Chwa => match smc.read_key(b"CHWA")?.valid_bytes() {
+                [0] => LimitState::Unlimited,
+                _ => LimitState::Limited(ChargeLimit::new(80)),
+            },
+            ChargeScheme::Chte | ChargeScheme::Ch0b => {
+                if smc.charging_inhibited()? {
+                    LimitState::Inhibited
+                } else {
+                    LimitState::Unlimited
+                }
+            }
+        };
+        Ok(Self {
+            smc,
+            clock: SystemClock,
+            scheme,
+            state,
+        })
+    }
+}
+
+impl<'a, T: SmcTransport, C: Clock> ChargeLimiter<'a, T, C> {
+    /// Replaces the clock, e.g. with a
+    /// [`ManualClock`](crate::watch::ManualClock) in tests.
+    pub fn with_clock<D: Clock>(self, clock: D) -> ChargeLimiter<'a, T, D> {
+        ChargeLimiter {
+            smc: self.smc,
+            clock,
+            scheme: self.scheme,
+            state: self.state,
+        }
+    }
+
+    /// Returns the scheme used by the limiter.
+    pub fn scheme(&self) -> ChargeScheme {
+        self.scheme
+    }
+
+    /// Returns the state of the limit.
+    pub fn state(&self) -> LimitState {
+        self.state
+    }
+
+    /// Returns the limit, `None` if there is none or it is not known, see
+    /// [`state`](Self::state).
+    pub fn limit(&self) -> Option<ChargeLimit> {
+        match self.state {
+            LimitState::Limited(limit) => Some(limit),
+            LimitState::Unlimited | LimitState::Inhibited => None,
+        }
+    }
+
+    /// Sets the limit and [applies](Self::apply) it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChargeError::LimitOutOfRange`] if the scheme does not
+    /// support the limit, see [`ChargeScheme::range`], and the errors of
+    /// [`apply`](Self::apply). The state is not changed on errors.
+    pub fn set_limit(&mut self, limit: ChargeLimit) -> Result<(), ChargeError> {
+        let range = self.scheme.range();
+        if !range.contains(&limit.percent) {
+            return Err(ChargeError::LimitOutOfRange {
+                percent: limit.percent,
+                min: *range.start(),
+                max: *range.end(),
+            });
+        }
+        self.apply_limit(limit)?;
+        self.state = LimitState::Limited(limit);
+        Ok(())
+    }
+
+    /// Removes the limit, the battery charges to 100%.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`IOService::write_key`], the state is not
+    /// changed then.
+    pub fn clear_limit(&mut self) -> Result<(), ChargeError> {
+        match self.scheme {
+            ChargeScheme::Bclm => self.smc.write_key(b"BCLM", &[100])?,
+            ChargeScheme::Chwa => self.smc.write_key(b"CHWA", &[0])?,
+            scheme => self.smc.inhibit_charging(scheme, false)?,
+        }
+        self.state = LimitState::Unlimited;
+        Ok(())
+    }
+
+    /// Writes the limit to the SMC.
+    ///
+    /// With a scheme that is not [native](ChargeScheme::is_native), charging
+    /// is inhibited if the charge reached the limit, and allowed again once
+    /// the charge dropped by the hysteresis. Does nothing without a limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChargeError::NoBattery`] if the charge of the battery can
+    /// not be read, and the errors of [`IOService::write_key`].
+    pub fn apply(&self) -> Result<(), ChargeError> {
+        match self.state {
+            LimitState::Limited(limit) => self.apply_limit(limit),
+            LimitState::Unlimited | LimitState::Inhibited => Ok(()),
+        }
+    }
+
+    fn apply_limit(&self, limit: ChargeLimit) -> Result<(), ChargeError> {
+        match self.scheme {
+            ChargeScheme::Bclm => self.smc.write_key(b"BCLM", &[limit.percent])?,
+            ChargeScheme::Chwa => self.smc.write_key(b"CHWA", &[1])?,
+            scheme => {
+                let charge = self
+                    .smc
+                    .battery()?
+                    .and_then(|b| b.charge)
+                    .ok_or(ChargeError::NoBattery)?;
+                let resume = limit.percent.saturating_sub(limit.hysteresis);
+                if charge >= f64::from(limit.percent) {
+                    self.smc.inhibit_charging(scheme, true)?;
+                } else if charge <= f64::from(resume) {
+                    self.smc.inhibit_charging(scheme, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the limit until `f` returns [`ControlFlow::Break`].
+    ///
+    /// The limit is applied at once, then the charge (`BRSC`, or `B0RM` on
+    /// Macs without it) is read every `interval` with a [`Watcher`], and
+    /// the limit is applied again when it changes. `f` receives the result
+    /// of each application, and the errors of reading the charge.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn run(
+        &self,
+        interval: Duration,
+        mut f: impl FnMut(Result<(), ChargeError>) -> ControlFlow<()>,
+    ) {
+        let charge = match self.smc.has_keys(&[b"BRSC"]) {
+            Ok(false) => b"B0RM",
+            _ => b"BRSC",
+        };
+        let mut watcher = Watcher::new(self.smc)
+            .with_clock(&self.clock)
+            .watch(charge, interval);
+        // the first read only records the charge, apply it after
+        for event in watcher.poll() {
+            if let WatchEvent::Failed { error, .. } = event
+                && f(Err(error.into())).is_break()
+            {
+                return;
+            }
+        }
+        if f(self.apply()).is_break() {
+            return;
+        }
+        watcher.run(|event| match event {
+            WatchEvent::Changed { .. } => f(self.apply()),
+            WatchEvent::Failed { error, .. } => f(Err(error.into())),
+        });
+    }
+}
+
+#[test]
+fn charge_limit() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::{SMC_ATTR_READ, SMC_ATTR_WRITE};
+    use crate::watch::ManualClock;
+
+    let rw = SMC_ATTR_READ | SMC_ATTR_WRITE;
+    let smc = IOService::with_transport(MockSmc::new());
+    assert_eq!(smc.charge_scheme(), Ok(None));
+    assert!(matches!(
+        ChargeLimiter::new(&smc),
+        Err(ChargeError::Unsupported)
+    ));
+    assert_eq!(smc.charging_inhibited(), Err(ChargeError::Unsupported));
+
+    // Intel, the limit is kept by the SMC
+    let mock = MockSmc::new();
+    mock.insert(*b"BCLM", MockKey::new(b"ui8 ", rw, &[100]));
+    let smc = IOService::with_transport(mock);
+    let mut limiter = ChargeLimiter::new(&smc).expect("BCLM exists");
+    assert_eq!(limiter.state(), LimitState::Unlimited);
+    assert_eq!(
+        limiter.set_limit(ChargeLimit::new(10)),
+        Err(ChargeError::LimitOutOfRange {
+            percent: 10,
+            min: 20,
+            max: 100
+        })
+    );
+    // writing needs root, a failed write keeps the state
+    assert_eq!(
+        limiter.set_limit(ChargeLimit::new(60)),
+        Err(ChargeError::Smc(SmcError::PrivilegeViolation))
+    );
+    assert_eq!(limiter.state(), LimitState::Unlimited);
+    smc.transport().set_privileged(true);
+    assert_eq!(limiter.set_limit(ChargeLimit::new(60)), Ok(()));
+    let mut limiter = ChargeLimiter::new(&smc).expect("BCLM exists");
+    assert_eq!(limiter.limit().map(|l| l.percent), Some(60));
+    assert_eq!(smc.charging_inhibited(), Ok(false));
+    assert_eq!(limiter.clear_limit(), Ok(()));
+    assert_eq!(limiter.limit(), None);
+    assert_eq!(smc.read_key(b"BCLM").map(|v| v.bytes[0]), Ok(100));
+
+    // Apple Silicon with older firmware, CHWA is preferred to CH0B
+    let mock = MockSmc::new();
+    mock.insert(*b"CHWA", MockKey::new(b"ui8 ", rw, &[0]));
+    mock.insert(*b"CH0B", MockKey::new(b"hex_", rw, &[0]));
+    mock.insert(*b"CH0C", MockKey::new(b"hex_", rw, &[0]));
+    mock.set_privileged(true);
+    let smc = IOService::with_transport(mock);
+    let mut limiter = ChargeLimiter::new(&smc).expect("CHWA exists");
+    assert_eq!(limiter.scheme(), ChargeScheme::Chwa);
+    assert!(limiter.set_limit(ChargeLimit::new(70)).is_err());
+    assert_eq!(limiter.set_limit(ChargeLimit::new(80)), Ok(()));
+    assert_eq!(smc.read_key(b"CHWA").map(|v| v.bytes[0]), Ok(1));
+    smc.transport().remove(*b"CHWA");
+    assert_eq!(smc.charge_scheme(), Ok(Some(ChargeScheme::Ch0b)));
+
+    // Apple Silicon with recent firmware, the limiter keeps the limit
+    let mock = MockSmc::new();
+    mock.insert(*b"CHTE", MockKey::new(b"ui32", rw, &[0; 4]));
+    mock.set_privileged(true);
+    let smc = IOService::with_transport(mock);
+    let set_charge = |percent: u16| {
+        let key = MockKey::new(b"ui16", SMC_ATTR_READ, &percent.to_le_bytes());
+        smc.transport().insert(*b"BRSC", key);
+    };
+    let clock = ManualClock::new();
+    let mut limiter = ChargeLimiter::new(&smc)
+        .expect("CHTE exists")
+        .with_clock(&clock);
+    assert_eq!(limiter.state(), LimitState::Unlimited);
+    let limit = ChargeLimit::new(80).with_hysteresis(10);
+    assert_eq!(limiter.set_limit(limit), Err(ChargeError::NoBattery));
+    assert_eq!(limiter.state(), LimitState::Unlimited);
+
+    set_charge(50);
+    assert_eq!(limiter.set_limit(limit), Ok(()));
+    let start = clock.now();
+    let mut charges = [79, 80, 75, 70, 75, 90].into_iter();
+    let mut inhibited = Vec::new();
+    limiter.run(Duration::from_secs(60), |result| {
+        assert_eq!(result, Ok(()));
+        inhibited.push(smc.charging_inhibited().expect("CHTE is readable"));
+        match charges.next() {
+            Some(charge) => {
+                set_charge(charge);
+                ControlFlow::Continue(())
+            }
+            None => ControlFlow::Break(()),
+        }
+    });
+    assert_eq!(inhibited, [false, false, true, true, false, false, true]);
+    assert_eq!(clock.now(), start + Duration::from_secs(6 * 60));
+    assert_eq!(
+        smc.read_key(b"CHTE").map(|v| v.valid_bytes().to_vec()),
+        Ok(vec![1, 0, 0, 0])
+    );
+    assert_eq!(limiter.limit().map(|l| l.percent), Some(80));
+
+    // charging stays off without the limiter running, a new limiter sees it
+    assert_eq!(smc.charging_inhibited(), Ok(true));
+    let mut limiter = ChargeLimiter::new(&smc).expect("CHTE exists");
+    assert_eq!(limiter.state(), LimitState::Inhibited);
+    assert_eq!(limiter.limit(), None);
+    assert_eq!(limiter.apply(), Ok(()));
+    assert_eq!(smc.charging_inhibited(), Ok(true));
+    assert_eq!(limiter.clear_limit(), Ok(()));
+    assert_eq!(limiter.state(), LimitState::Unlimited);
+    assert_eq!(smc.charging_inhibited(), Ok(false));
+}
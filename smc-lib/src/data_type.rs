@@ -0,0 +1,303 @@
+//! SMC data types.
+
+/// Data type of a SMC key, a four-character code such as `flt ` or `ui16`.
+///
+/// Codes that are not known by this crate are kept as [`DataType::Unknown`],
+/// so every code round-trips with both the `[u8; 4]` and the big endian
+/// `u32` representation.
+///
+/// See [AsahiLinux Docs](https://asahilinux.org/docs/hw/soc/smc) for common data types.
+///
+/// # Example
+///
+/// ```
+/// use smc_lib::data_type::{DataType, FixedPoint};
+///
+/// let data_type = DataType::from(*b"flt ");
+/// assert_eq!(data_type, DataType::Flt);
+/// assert_eq!(data_type.size(), Some(4));
+/// assert_eq!(u32::from(data_type), u32::from_be_bytes(*b"flt "));
+///
+/// let data_type = DataType::from(*b"sp78");
+/// assert_eq!(data_type, DataType::Sp(FixedPoint::new(7, 8).unwrap()));
+///
+/// let data_type = DataType::from(*b"abcd");
+/// assert_eq!(data_type, DataType::Unknown(*b"abcd"));
+/// assert_eq!(data_type.to_string(), "abcd");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// `flt `, 32-bit IEEE float
+    Flt,
+    /// `ui8 `
+    Ui8,
+    /// `ui16`
+    Ui16,
+    /// `ui32`
+    Ui32,
+    /// `ui64`
+    Ui64,
+    /// `si8 `
+    Si8,
+    /// `si16`
+    Si16,
+    /// `si32`
+    Si32,
+    /// `si64`
+    Si64,
+    /// `flag`, boolean
+    Flag,
+    /// `ch8*`, string of any length
+    Ch8,
+    /// `ioft`, 48.16 unsigned fixed point
+    Ioft,
+    /// `hex_`, opaque bytes of any length
+    Hex,
+    /// `fpXY`, 16-bit unsigned fixed point with `X` integer and `Y`
+    /// fraction bits
+    Fp(FixedPoint<16>),
+    /// `spXY`, 16-bit signed fixed point with a sign bit, `X` integer and
+    /// `Y` fraction bits
+    Sp(FixedPoint<15>),
+    /// `pwm `, 16-bit PWM duty cycle
+    Pwm,
+    /// `{fds`, fan descriptor
+    Fds,
+    /// `{rev`, firmware revision
+    Rev,
+    /// `{lim`, power limits
+    Lim,
+    /// `{ala`, ambient light sensor configuration
+    Ala,
+    /// `{lso`, light sensor output
+    Lso,
+    /// Any other code
+    Unknown([u8; 4]),
+}
+
+impl Default for DataType {
+    fn default() -> Self {
+        Self::Unknown([0; 4])
+    }
+}
+
+impl DataType {
+    /// Returns the four bytes of the code.
+    pub fn to_bytes(self) -> [u8; 4] {
+        match self {
+            Self::Flt => *b"flt ",
+            Self::Ui8 => *b"ui8 ",
+            Self::Ui16 => *b"ui16",
+            Self::Ui32 => *b"ui32",
+            Self::Ui64 => *b"ui64",
+            Self::Si8 => *b"si8 ",
+            Self::Si16 => *b"si16",
+            Self::Si32 => *b"si32",
+            Self::Si64 => *b"si64",
+            Self::Flag => *b"flag",
+            Self::Ch8 => *b"ch8*",
+            Self::Ioft => *b"ioft",
+            Self::Hex => *b"hex_",
+            Self::Fp(bits) => [
+                b'f',
+                b'p',
+                hex_digit(bits.integer),
+                hex_digit(bits.fraction),
+            ],
+            Self::Sp(bits) => [
+                b's',
+                b'p',
+                hex_digit(bits.integer),
+                hex_digit(bits.fraction),
+            ],
+            Self::Pwm => *b"pwm ",
+            Self::Fds => *b"{fds",
+            Self::Rev => *b"{rev",
+            Self::Lim => *b"{lim",
+            Self::Ala => *b"{ala",
+            Self::Lso => *b"{lso",
+            Self::Unknown(code) => code,
+        }
+    }
+
+    /// Returns the size in bytes of a value of this type.
+    ///
+    /// Returns `None` if the size is not fixed, or not known.
+    pub fn size(self) -> Option<u32> {
+        let size = match self {
+            Self::Ui8 | Self::Si8 | Self::Flag => 1,
+            Self::Ui16 | Self::Si16 | Self::Fp { .. } | Self::Sp { .. } | Self::Pwm => 2,
+            Self::Flt | Self::Ui32 | Self::Si32 => 4,
+            Self::Ui64 | Self::Si64 | Self::Ioft => 8,
+            Self::Lim => 3,
+            Self::Rev => 6,
+            Self::Fds => 16,
+            Self::Ch8 | Self::Hex | Self::Ala | Self::Lso | Self::Unknown(_) => return None,
+        };
+        Some(size)
+    }
+
+    /// Returns `false` for [`DataType::Unknown`].
+    pub fn is_known(self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+}
+
+/// Integer and fraction bits of the fixed point types [`DataType::Fp`] and
+/// [`DataType::Sp`], which have `BITS` bits without the sign.
+///
+/// Both are written as one hex digit of the code, so both are below 16 and
+/// they sum up to `BITS`.
+///
+/// # Example
+///
+/// ```
+/// use smc_lib::data_type::{DataType, FixedPoint};
+///
+/// let bits = FixedPoint::new(14, 2).unwrap();
+/// assert_eq!(DataType::Fp(bits).to_bytes(), *b"fpe2");
+/// assert_eq!(bits.fraction(), 2);
+/// assert_eq!(FixedPoint::<16>::new(16, 0), None);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FixedPoint<const BITS: u8> {
+    integer: u8,
+    fraction: u8,
+}
+
+impl<const BITS: u8> FixedPoint<BITS> {
+    /// Returns `None` if `integer` or `fraction` is above 15, or they do not
+    /// sum up to `BITS`.
+    pub const fn new(integer: u8, fraction: u8) -> Option<Self> {
+        if integer < 16 && fraction < 16 && integer + fraction == BITS {
+            Some(Self { integer, fraction })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of integer bits.
+    pub const fn integer(self) -> u8 {
+        self.integer
+    }
+
+    /// Returns the number of fraction bits.
+    pub const fn fraction(self) -> u8 {
+        self.fraction
+    }
+}
+
+fn hex_digit(n: u8) -> u8 {
+    b"0123456789abcdef"[usize::from(n)]
+}
+
+/// Parses the `XY` of `fpXY` and `spXY`.
+fn fixed_point_bits<const BITS: u8>(x: u8, y: u8) -> Option<FixedPoint<BITS>> {
+    // only lowercase digits are used by the SMC
+    if x.is_ascii_uppercase() || y.is_ascii_uppercase() {
+        return None;
+    }
+    let integer = (x as char).to_digit(16)? as u8;
+    let fraction = (y as char).to_digit(16)? as u8;
+    FixedPoint::new(integer, fraction)
+}
+
+impl From<[u8; 4]> for DataType {
+    fn from(code: [u8; 4]) -> Self {
+        match &code {
+            b"flt " => Self::Flt,
+            b"ui8 " => Self::Ui8,
+            b"ui16" => Self::Ui16,
+            b"ui32" => Self::Ui32,
+            b"ui64" => Self::Ui64,
+            b"si8 " => Self::Si8,
+            b"si16" => Self::Si16,
+            b"si32" => Self::Si32,
+            b"si64" => Self::Si64,
+            b"flag" => Self::Flag,
+            b"ch8*" => Self::Ch8,
+            b"ioft" => Self::Ioft,
+            b"hex_" => Self::Hex,
+            b"pwm " => Self::Pwm,
+            b"{fds" => Self::Fds,
+            b"{rev" => Self::Rev,
+            b"{lim" => Self::Lim,
+            b"{ala" => Self::Ala,
+            b"{lso" => Self::Lso,
+            [b'f', b'p', x, y] => match fixed_point_bits(*x, *y) {
+                Some(bits) => Self::Fp(bits),
+                None => Self::Unknown(code),
+            },
+            [b's', b'p', x, y] => match fixed_point_bits(*x, *y) {
+                Some(bits) => Self::Sp(bits),
+                None => Self::Unknown(code),
+            },
+            _ => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<&[u8; 4]> for DataType {
+    fn from(code: &[u8; 4]) -> Self {
+        Self::from(*code)
+    }
+}
+
+impl From<u32> for DataType {
+    fn from(code: u32) -> Self {
+        Self::from(code.to_be_bytes())
+    }
+}
+
+impl From<DataType> for [u8; 4] {
+    fn from(data_type: DataType) -> Self {
+        data_type.to_bytes()
+    }
+}
+
+impl From<DataType> for u32 {
+    fn from(data_type: DataType) -> Self {
+        u32::from_be_bytes(data_type.to_bytes())
+    }
+}
+
+impl std::fmt::Display for DataType {
+    /// Writes the four characters of the code, including trailing spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+#[test]
+fn round_trip() {
+    let mut codes = vec![*b"flt ", *b"ch8*", *b"{fds", *b"pwm ", *b"\0\0\0\0"];
+    for x in b"0123456789abcdefA".iter() {
+        for y in b"0123456789abcdefA".iter() {
+            codes.push([b'f', b'p', *x, *y]);
+            codes.push([b's', b'p', *x, *y]);
+        }
+    }
+    for code in codes {
+        let data_type = DataType::from(code);
+        assert_eq!(data_type.to_bytes(), code);
+        assert_eq!(DataType::from(u32::from(data_type)), data_type);
+    }
+    assert_eq!(
+        DataType::from(*b"fpe2"),
+        DataType::Fp(FixedPoint {
+            integer: 14,
+            fraction: 2
+        })
+    );
+    assert_eq!(DataType::from(*b"fp78"), DataType::Unknown(*b"fp78"));
+    // bits that do not fit a hex digit can not be a type
+    assert_eq!(FixedPoint::<16>::new(16, 0), None);
+    assert_eq!(FixedPoint::<15>::new(0, 40), None);
+    assert_eq!(
+        FixedPoint::<15>::new(7, 8)
+            .map(DataType::Sp)
+            .map(DataType::to_bytes),
+        Some(*b"sp78")
+    );
+    assert_eq!(DataType::from(*b"fpE2"), DataType::Unknown(*b"fpE2"));
+}
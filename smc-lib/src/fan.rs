@@ -0,0 +1,500 @@
+//! Fans, read from the `FNum` and `F{n}..` keys, and manual fan control with
+//! [`FanControl`].
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::io::IOService;
+//! use smc_lib::mock::MockSmc;
+//!
+//! let smc = IOService::with_transport(MockSmc::sample());
+//! for fan in smc.fans().unwrap() {
+//!     println!("fan {}: {:?} rpm", fan.index, fan.actual);
+//! }
+//! ```
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::key::{INDEXED_KEY_LIMIT, SmcKey};
+use crate::structs::SMCVal;
+use crate::value::SmcValue;
+
+/// Control mode of a fan, the `F{n}Md` key, or the bit of the fan in `FS! `
+/// on older Intel Macs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FanMode {
+    /// The speed is chosen by the SMC, `0`.
+    Auto,
+    /// The speed follows `F{n}Tg`, `1`.
+    Forced,
+    /// Any other mode.
+    Other(u8),
+}
+
+impl From<u8> for FanMode {
+    fn from(mode: u8) -> Self {
+        match mode {
+            0 => Self::Auto,
+            1 => Self::Forced,
+            mode => Self::Other(mode),
+        }
+    }
+}
+
+impl From<FanMode> for u8 {
+    fn from(mode: FanMode) -> Self {
+        match mode {
+            FanMode::Auto => 0,
+            FanMode::Forced => 1,
+            FanMode::Other(mode) => mode,
+        }
+    }
+}
+
+impl std::fmt::Display for FanMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FanMode::Auto => write!(f, "auto"),
+            FanMode::Forced => write!(f, "forced"),
+            FanMode::Other(mode) => write!(f, "mode {}", mode),
+        }
+    }
+}
+
+/// Errors returned by [`IOService::control_fan`] and [`FanControl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanError {
+    /// The fan index has no keys, the keys are numbered up to
+    /// [`INDEXED_KEY_LIMIT`].
+    IndexOutOfRange { index: u8, max: u8 },
+    /// The requested speed is outside of the range of the fan, in RPM.
+    SpeedOutOfRange { rpm: u32, min: u32, max: u32 },
+    /// A key of the fan can not be read or written.
+    Smc(SmcError),
+}
+
+impl From<SmcError> for FanError {
+    fn from(err: SmcError) -> Self {
+        FanError::Smc(err)
+    }
+}
+
+impl std::fmt::Display for FanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FanError::IndexOutOfRange { index, max } => {
+                write!(f, "fan index {} is out of range, 0..={}", index, max)
+            }
+            FanError::SpeedOutOfRange { rpm, min, max } => write!(
+                f,
+                "fan speed {} rpm is out of range, {}..={} rpm",
+                rpm, min, max
+            ),
+            FanError::Smc(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for FanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FanError::IndexOutOfRange { .. } | FanError::SpeedOutOfRange { .. } => None,
+            FanError::Smc(err) => Some(err),
+        }
+    }
+}
+
+/// State of a fan, returned by [`IOService::fans`].
+///
+/// Speeds are in RPM, whether the SMC stores them as `flt ` or as `fpe2`.
+/// Keys that are missing on this Mac are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FanInfo {
+    /// The `n` of the `F{n}..` keys.
+    pub index: u8,
+    /// Actual speed, `F{n}Ac`.
+    pub actual: Option<f64>,
+    /// Minimum speed, `F{n}Mn`.
+    pub min: Option<f64>,
+    /// Maximum speed, `F{n}Mx`.
+    pub max: Option<f64>,
+    /// Target speed, `F{n}Tg`.
+    pub target: Option<f64>,
+    pub mode: Option<FanMode>,
+    /// Name from the fan descriptor `F{n}ID`, e.g. `"Left Side"`.
+    pub label: Option<String>,
+}
+
+/// Returns the key `F{index}{suffix}`.
+pub(crate) fn fan_key(index: u8, suffix: &[u8; 2]) -> SmcKey {
+    SmcKey::from([b'F', b'0' + index, suffix[0], suffix[1]])
+}
+
+impl<T: SmcTransport> IOService<T> {
+    /// Returns the fans listed by `FNum`.
+    ///
+    /// At most [`INDEXED_KEY_LIMIT`] fans are returned, the keys of fans with
+    /// a higher index can not be named.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `FNum` can not be read, or is not a `ui8 `, and
+    /// if a key of a fan exists but can not be read. Keys that do not exist
+    /// are `None` in [`FanInfo`].
+    pub fn fans(&self) -> Result<Vec<FanInfo>, SmcError> {
+        let fnum = self.read_key(b"FNum")?;
+        let count = match fnum.data_value() {
+            Some(SmcValue::U8(count)) => count.min(INDEXED_KEY_LIMIT),
+            _ => {
+                return Err(SmcError::UnexpectedType {
+                    key: fnum.key,
+                    data_type: fnum.data_type,
+                });
+            }
+        };
+        let mut forced = None;
+        (0..count)
+            .map(|index| {
+                let mode = match self.read_optional(fan_key(index, b"Md"))? {
+                    Some(SmcValue::U8(mode)) => Some(FanMode::from(mode)),
+                    _ => {
+                        if forced.is_none() {
+                            forced = Some(self.read_optional(b"FS! ")?);
+                        }
+                        match forced.as_ref().and_then(Option::as_ref) {
+                            Some(SmcValue::U16(bits)) => Some(if bits & (1 << index) != 0 {
+                                FanMode::Forced
+                            } else {
+                                FanMode::Auto
+                            }),
+                            _ => None,
+                        }
+                    }
+                };
+                let label = match self.read_optional(fan_key(index, b"ID"))? {
+                    Some(SmcValue::FanDescriptor(fds)) => Some(fds.name),
+                    _ => None,
+                };
+                Ok(FanInfo {
+                    index,
+                    actual: self.read_number(fan_key(index, b"Ac"))?,
+                    min: self.read_number(fan_key(index, b"Mn"))?,
+                    max: self.read_number(fan_key(index, b"Mx"))?,
+                    target: self.read_number(fan_key(index, b"Tg"))?,
+                    mode,
+                    label,
+                })
+            })
+            .collect()
+    }
+
+    /// Forces fan `index` to `rpm`, until the returned guard is dropped.
+    ///
+    /// `rpm` must be within `F{n}Mn` and `F{n}Mx`. The fan is switched to
+    /// manual mode with `F{n}Md`, or its bit in `FS! ` on Macs without
+    /// `F{n}Md`, and its target speed `F{n}Tg` is set. Writing needs root.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FanError::IndexOutOfRange`] if `index` is not below
+    /// [`INDEXED_KEY_LIMIT`], [`FanError::SpeedOutOfRange`] if `rpm` is out
+    /// of range, `FanError::Smc(SmcError::KeyNotFound)` if the fan or its
+    /// range does not exist, and the errors of [`IOService::write_key`]. Keys written before an error
+    /// are restored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
+    /// use smc_lib::io::IOService;
+    ///
+    /// let smc = IOService::init().unwrap();
+    /// let fan = smc.control_fan(0, 3000).unwrap();
+    /// // run the benchmark, the fan is restored when `fan` is dropped
+    /// drop(fan);
+    /// # }
+    /// ```
+    pub fn control_fan(&self, index: u8, rpm: u32) -> Result<FanControl<'_, T>, FanError> {
+        if index >= INDEXED_KEY_LIMIT {
+            return Err(FanError::IndexOutOfRange {
+                index,
+                max: INDEXED_KEY_LIMIT - 1,
+            });
+        }
+        let range = |suffix| {
+            self.read_number(fan_key(index, suffix))?
+                .ok_or(SmcError::KeyNotFound)
+                .map(|rpm| rpm.round() as u32)
+        };
+        let (min, max) = (range(b"Mn")?, range(b"Mx")?);
+        let target = self.read_key(fan_key(index, b"Tg"))?;
+        let mode = match self.read_key(fan_key(index, b"Md")) {
+            Ok(mode) => SavedMode::Key(mode),
+            Err(SmcError::KeyNotFound) => SavedMode::ForcedBit(self.forced_bit(index)?),
+            Err(e) => return Err(e.into()),
+        };
+        let mut control = FanControl {
+            smc: self,
+            index,
+            min,
+            max,
+            target,
+            mode,
+            restored: false,
+        };
+        control.set_speed(rpm)?;
+        match control.mode {
+            SavedMode::Key(_) => self.write_value(fan_key(index, b"Md"), 1u8)?,
+            SavedMode::ForcedBit(_) => self.set_forced_bit(index, true)?,
+        }
+        Ok(control)
+    }
+
+    /// Returns the bit of fan `index` in `FS! `.
+    fn forced_bit(&self, index: u8) -> Result<bool, SmcError> {
+        Ok(self.forced_bits()? & (1 << index) != 0)
+    }
+
+    /// Returns `FS! `, one bit per forced fan.
+    fn forced_bits(&self) -> Result<u16, SmcError> {
+        let val = self.read_key(b"FS! ")?;
+        match val.data_value() {
+            Some(SmcValue::U16(bits)) => Ok(bits),
+            _ => Err(SmcError::UnexpectedType {
+                key: val.key,
+                data_type: val.data_type,
+            }),
+        }
+    }
+
+    /// Sets the bit of fan `index` in `FS! `, keeping the other fans.
+    fn set_forced_bit(&self, index: u8, forced: bool) -> Result<(), SmcError> {
+        let bits = self.forced_bits()?;
+        let bits = if forced {
+            bits | (1 << index)
+        } else {
+            bits & !(1 << index)
+        };
+        self.write_value(b"FS! ", bits)
+    }
+}
+
+/// How the mode of a fan was before [`FanControl`] changed it.
+enum SavedMode {
+    /// The `F{n}Md` key.
+    Key(SMCVal),
+    /// The bit of the fan in `FS! `.
+    ForcedBit(bool),
+}
+
+/// A fan forced to a speed, returned by [`IOService::control_fan`].
+///
+/// When the guard is dropped, including while unwinding from a panic, the
+/// previous mode and target speed of the fan are written back. Use
+/// [`restore`](Self::restore) to see whether restoring failed. Nothing is
+/// restored if the process aborts or is killed.
+pub struct FanControl<'a, T: SmcTransport> {
+    smc: &'a IOService<T>,
+    index: u8,
+    min: u32,
+    max: u32,
+    /// `F{n}Tg` before the fan was forced
+    target: SMCVal,
+    mode: SavedMode,
+    restored: bool,
+}
+
+impl<T: SmcTransport> FanControl<'_, T> {
+    /// Returns the index of the fan.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Returns the allowed speeds, `F{n}Mn` and `F{n}Mx` in RPM.
+    pub fn range(&self) -> std::ops::RangeInclusive<u32> {
+        self.min..=self.max
+    }
+
+    /// Changes the target speed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FanError::SpeedOutOfRange`] if `rpm` is out of
+    /// [`range`](Self::range), and the errors of [`IOService::write_key`].
+    pub fn set_speed(&mut self, rpm: u32) -> Result<(), FanError> {
+        if !self.range().contains(&rpm) {
+            return Err(FanError::SpeedOutOfRange {
+                rpm,
+                min: self.min,
+                max: self.max,
+            });
+        }
+        self.smc
+            .write_value(fan_key(self.index, b"Tg"), SmcValue::F32(rpm as f32))?;
+        Ok(())
+    }
+
+    /// Restores the previous mode and target speed of the fan.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the writes, every write is tried anyway.
+    pub fn restore(mut self) -> Result<(), SmcError> {
+        self.restore_inner()
+    }
+
+    fn restore_inner(&mut self) -> Result<(), SmcError> {
+        self.restored = true;
+        let key = fan_key(self.index, b"Tg");
+        let target = self.smc.write_key(key, self.target.valid_bytes());
+        let mode = match &self.mode {
+            SavedMode::Key(mode) => self.smc.write_key(mode.key, mode.valid_bytes()),
+            SavedMode::ForcedBit(forced) => self.smc.set_forced_bit(self.index, *forced),
+        };
+        target.and(mode)
+    }
+}
+
+impl<T: SmcTransport> Drop for FanControl<'_, T> {
+    fn drop(&mut self) {
+        if !self.restored {
+            // errors can not be reported from drop, see `restore`
+            let _ = self.restore_inner();
+        }
+    }
+}
+
+#[test]
+fn fans() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::SMC_ATTR_READ;
+
+    let r = SMC_ATTR_READ;
+    let mock = MockSmc::sample();
+    mock.insert(*b"FNum", MockKey::new(b"ui8 ", r, &[2]));
+    // Apple Silicon
+    mock.insert(*b"F0Mn", MockKey::new(b"flt ", r, &1000f32.to_le_bytes()));
+    mock.insert(*b"F0Mx", MockKey::new(b"flt ", r, &6000f32.to_le_bytes()));
+    mock.insert(*b"F0Tg", MockKey::new(b"flt ", r, &1200f32.to_le_bytes()));
+    mock.insert(*b"F0Md", MockKey::new(b"ui8 ", r, &[3]));
+    let mut fds = [0u8; 16];
+    fds[4..13].copy_from_slice(b"Left Side");
+    mock.insert(*b"F0ID", MockKey::new(b"{fds", r, &fds));
+    // Intel, without F1Md
+    let fpe2 = |rpm: u16| (rpm << 2).to_le_bytes();
+    mock.insert(*b"F1Ac", MockKey::new(b"fpe2", r, &fpe2(2160)));
+    mock.insert(*b"F1Mn", MockKey::new(b"fpe2", r, &fpe2(2000)));
+    mock.insert(*b"F1Mx", MockKey::new(b"fpe2", r, &fpe2(6156)));
+    mock.insert(*b"FS! ", MockKey::new(b"ui16", r, &2u16.to_le_bytes()));
+    let smc = IOService::with_transport(mock);
+
+    let fans = smc.fans().expect("fans can be read");
+    assert_eq!(
+        fans,
+        [
+            FanInfo {
+                index: 0,
+                actual: Some(1200.0),
+                min: Some(1000.0),
+                max: Some(6000.0),
+                target: Some(1200.0),
+                mode: Some(FanMode::Other(3)),
+                label: Some("Left Side".to_owned()),
+            },
+            FanInfo {
+                index: 1,
+                actual: Some(2160.0),
+                min: Some(2000.0),
+                max: Some(6156.0),
+                target: None,
+                mode: Some(FanMode::Forced),
+                label: None,
+            },
+        ]
+    );
+
+    smc.transport().remove(*b"F1Ac");
+    let fans = smc.fans().expect("fans can be read");
+    assert_eq!(
+        fans.iter().map(|f| f.actual).collect::<Vec<_>>(),
+        [Some(1200.0), None]
+    );
+    smc.transport()
+        .insert(*b"FNum", MockKey::new(b"flt ", r, &2f32.to_le_bytes()));
+    assert_eq!(
+        smc.fans(),
+        Err(SmcError::UnexpectedType {
+            key: SmcKey::new(b"FNum"),
+            data_type: crate::data_type::DataType::Flt
+        })
+    );
+}
+
+#[test]
+fn fan_control() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::{SMC_ATTR_READ, SMC_ATTR_WRITE};
+
+    let rw = SMC_ATTR_READ | SMC_ATTR_WRITE;
+    let mock = MockSmc::sample();
+    mock.insert(*b"FNum", MockKey::new(b"ui8 ", rw, &[2]));
+    mock.insert(*b"F0Mn", MockKey::new(b"flt ", rw, &1000f32.to_le_bytes()));
+    mock.insert(*b"F0Mx", MockKey::new(b"flt ", rw, &6000f32.to_le_bytes()));
+    mock.insert(*b"F0Tg", MockKey::new(b"flt ", rw, &1200f32.to_le_bytes()));
+    mock.insert(*b"F0Md", MockKey::new(b"ui8 ", rw, &[0]));
+    // Intel, forced with `FS! `
+    let fpe2 = |rpm: u16| (rpm << 2).to_le_bytes();
+    mock.insert(*b"F1Ac", MockKey::new(b"fpe2", rw, &fpe2(2000)));
+    mock.insert(*b"F1Mn", MockKey::new(b"fpe2", rw, &fpe2(2000)));
+    mock.insert(*b"F1Mx", MockKey::new(b"fpe2", rw, &fpe2(6000)));
+    mock.insert(*b"F1Tg", MockKey::new(b"fpe2", rw, &fpe2(2000)));
+    mock.insert(*b"FS! ", MockKey::new(b"ui16", rw, &1u16.to_le_bytes()));
+    mock.set_privileged(true);
+    let smc = IOService::with_transport(mock);
+    let bytes = |key: &[u8; 4]| smc.read_key(key).map(|v| v.valid_bytes().to_vec());
+
+    assert_eq!(
+        smc.control_fan(0, 7000).map(|_| ()),
+        Err(FanError::SpeedOutOfRange {
+            rpm: 7000,
+            min: 1000,
+            max: 6000
+        })
+    );
+    assert_eq!(
+        smc.control_fan(2, 3000).map(|_| ()),
+        Err(FanError::Smc(SmcError::KeyNotFound))
+    );
+    assert_eq!(
+        smc.control_fan(10, 3000).map(|_| ()),
+        Err(FanError::IndexOutOfRange { index: 10, max: 9 })
+    );
+    let mut fan = smc.control_fan(0, 3000).expect("fan 0 can be forced");
+    assert_eq!(fan.range(), 1000..=6000);
+    assert_eq!(bytes(b"F0Md"), Ok(vec![1]));
+    assert_eq!(bytes(b"F0Tg"), Ok(3000f32.to_le_bytes().to_vec()));
+    assert!(fan.set_speed(500).is_err());
+    assert_eq!(fan.set_speed(4000), Ok(()));
+    drop(fan);
+    assert_eq!(bytes(b"F0Md"), Ok(vec![0]));
+    assert_eq!(bytes(b"F0Tg"), Ok(1200f32.to_le_bytes().to_vec()));
+
+    // restored while unwinding
+    let panicked = std::panic::catch_unwind(|| {
+        let _fan = smc.control_fan(1, 3000).expect("fan 1 can be forced");
+        assert_eq!(bytes(b"FS! "), Ok(vec![3, 0]));
+        assert_eq!(bytes(b"F1Tg"), Ok(fpe2(3000).to_vec()));
+        panic!("benchmark failed");
+    });
+    assert!(panicked.is_err());
+    assert_eq!(bytes(b"FS! "), Ok(vec![1, 0]));
+    assert_eq!(bytes(b"F1Tg"), Ok(fpe2(2000).to_vec()));
+
+    // a failed write restores the keys written before
+    smc.transport()
+        .insert(*b"F0Md", MockKey::new(b"ui8 ", SMC_ATTR_READ, &[0]));
+    assert!(smc.control_fan(0, 3000).is_err());
+    assert_eq!(bytes(b"F0Tg"), Ok(1200f32.to_le_bytes().to_vec()));
+    let fan = smc.control_fan(1, 2500).expect("fan 1 can be forced");
+    assert_eq!(fan.restore(), Ok(()));
+    assert_eq!(bytes(b"FS! "), Ok(vec![1, 0]));
+}
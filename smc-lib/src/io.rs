@@ -1,31 +1,273 @@
+use crate::data_type::DataType;
+use crate::key::SmcKey;
 use crate::structs::{
-    KERNEL_INDEX_SMC, SMC_BYTES_LEN, SMC_CMD_READ_BYTES, SMC_CMD_READ_INDEX, SMC_CMD_READ_KEYINFO,
-    SMC_CMD_WRITE_BYTES, SMCBytes, SMCKeyData, SMCKeyData_keyInfo, SMCVal,
-};
-use libc::{KERN_SUCCESS, mach_error_string, mach_port_t};
-use objc2_io_kit::{
-    IOConnectCallStructMethod, IOIteratorNext, IOMainPort, IOObjectRelease, IOServiceClose,
-    IOServiceGetMatchingServices, IOServiceMatching, IOServiceOpen, io_connect_t,
+    SMC_BYTES_LEN, SMC_CMD_READ_BYTES, SMC_CMD_READ_INDEX, SMC_CMD_READ_KEYINFO,
+    SMC_CMD_WRITE_BYTES, SMC_RESULT_BAD_ARGUMENT, SMC_RESULT_BAD_COMMAND,
+    SMC_RESULT_BAD_FUNC_PARAMETER, SMC_RESULT_BAD_PARAMETER, SMC_RESULT_COMM_COLLISION,
+    SMC_RESULT_DEVICE_ACCESS_ERROR, SMC_RESULT_ERROR, SMC_RESULT_EVENT_BUFF_READ_ERROR,
+    SMC_RESULT_EVENT_BUFF_WRONG_ORDER, SMC_RESULT_FRAMING_ERROR, SMC_RESULT_INVALID_SIZE,
+    SMC_RESULT_KEY_INDEX_RANGE_ERROR, SMC_RESULT_KEY_NOT_FOUND, SMC_RESULT_KEY_SIZE_MISMATCH,
+    SMC_RESULT_NOT_READABLE, SMC_RESULT_NOT_WRITABLE, SMC_RESULT_SMB_ACCESS_ERROR,
+    SMC_RESULT_SPURIOUS_DATA, SMC_RESULT_SUCCESS, SMC_RESULT_TIMEOUT,
+    SMC_RESULT_UNSUPPORTED_FEATURE, SMCBytes, SMCKeyData, SMCKeyData_keyInfo, SMCVal,
 };
+use crate::value::{ByteOrder, EncodeError, SmcValue};
 use std::{
     borrow::Cow,
-    ffi::{CStr, c_void},
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
-unsafe extern "C" {
-    pub fn mach_task_self() -> mach_port_t;
+/// Return code of kernel and IOKit calls.
+///
+/// This is the same type as `kern_return_t` on macOS, it is redefined here
+/// so that it is available on every platform.
+#[allow(non_camel_case_types)]
+pub type kern_return_t = i32;
+
+/// The call succeeded.
+pub const KERN_SUCCESS: kern_return_t = 0;
+/// An argument was invalid.
+pub const KERN_INVALID_ARGUMENT: kern_return_t = 4;
+/// The call failed for an unspecified reason.
+pub const KERN_FAILURE: kern_return_t = 5;
+/// The requested operation is not supported, e.g. the key does not exist.
+pub const KERN_NOT_SUPPORTED: kern_return_t = 46;
+/// IOKit: the caller does not have enough privilege.
+pub const IO_RETURN_NOT_PRIVILEGED: kern_return_t = 0xe00002c1_u32 as kern_return_t;
+/// IOKit: an argument was invalid.
+pub const IO_RETURN_BAD_ARGUMENT: kern_return_t = 0xe00002c2_u32 as kern_return_t;
+
+/// A channel able to exchange [`SMCKeyData`] with a SMC.
+///
+/// Every operation of [`IOService`] is a sequence of request/response pairs,
+/// so anything implementing this trait can be used as a SMC, e.g. the IOKit
+/// connection on macOS, or a fake one in tests.
+pub trait SmcTransport {
+    /// Sends `input` to the SMC and writes the response into `output`.
+    ///
+    /// The command is selected by `input.data8`. An `Err` means the request
+    /// could not be delivered, firmware level failures are reported in
+    /// `output.result`.
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError>;
+
+    /// Returns the byte order of the numbers stored in this SMC.
+    ///
+    /// Defaults to [`ByteOrder::native`].
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::native()
+    }
+}
+
+/// Errors returned by SMC operations.
+///
+/// This enum implements `Display` and [`std::error::Error`], so you can print
+/// it, or match on the cause of the failure. New causes may be added, so
+/// matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SmcError {
+    /// The key does not exist.
+    KeyNotFound,
+    /// The size of a value does not match the size of the key.
+    SizeMismatch { expected: u32, actual: u32 },
+    /// The value is longer than [`SMC_BYTES_LEN`].
+    ValueTooLong(usize),
+    /// The caller does not have enough privilege, writing usually needs root.
+    PrivilegeViolation,
+    /// The AppleSMC service can not be found.
+    ServiceNotFound,
+    /// The AppleSMC service can not be opened.
+    OpenFailed(kern_return_t),
+    /// A kernel or IOKit call failed with this code.
+    Transport(kern_return_t),
+    /// The SMC firmware rejected the command.
+    Firmware(FirmwareError),
+    /// The value can not be encoded as the data type of the key.
+    Encode(EncodeError),
+    /// The key does not have the data type its value is expected in.
+    UnexpectedType { key: SmcKey, data_type: DataType },
+}
+
+impl SmcError {
+    /// Converts the code of a failed kernel or IOKit call.
+    pub fn from_kern(code: kern_return_t) -> Self {
+        match code {
+            IO_RETURN_NOT_PRIVILEGED => Self::PrivilegeViolation,
+            code => Self::Transport(code),
+        }
+    }
+
+    /// Converts the `result` byte of a SMC response, `None` means success.
+    pub fn from_result(result: u8) -> Option<Self> {
+        match result {
+            SMC_RESULT_SUCCESS => None,
+            SMC_RESULT_KEY_NOT_FOUND => Some(Self::KeyNotFound),
+            result => Some(Self::Firmware(FirmwareError::from_code(result))),
+        }
+    }
+}
+
+/// Failures reported by the SMC firmware in [`SMCKeyData::result`].
+///
+/// A missing key is reported as [`SmcError::KeyNotFound`] instead of
+/// [`FirmwareError::KeyNotFound`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareError {
+    Error,
+    CommCollision,
+    SpuriousData,
+    BadCommand,
+    BadParameter,
+    KeyNotFound,
+    KeyNotReadable,
+    KeyNotWritable,
+    KeySizeMismatch,
+    FramingError,
+    BadArgument,
+    Timeout,
+    KeyIndexRange,
+    BadFuncParameter,
+    EventBufferWrongOrder,
+    EventBufferReadError,
+    DeviceAccessError,
+    UnsupportedFeature,
+    SmbAccessError,
+    InvalidSize,
+    /// A result byte that is not known
+    Unknown(u8),
 }
 
+impl FirmwareError {
+    /// Converts a non-zero `result` byte.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            SMC_RESULT_ERROR => Self::Error,
+            SMC_RESULT_COMM_COLLISION => Self::CommCollision,
+            SMC_RESULT_SPURIOUS_DATA => Self::SpuriousData,
+            SMC_RESULT_BAD_COMMAND => Self::BadCommand,
+            SMC_RESULT_BAD_PARAMETER => Self::BadParameter,
+            SMC_RESULT_KEY_NOT_FOUND => Self::KeyNotFound,
+            SMC_RESULT_NOT_READABLE => Self::KeyNotReadable,
+            SMC_RESULT_NOT_WRITABLE => Self::KeyNotWritable,
+            SMC_RESULT_KEY_SIZE_MISMATCH => Self::KeySizeMismatch,
+            SMC_RESULT_FRAMING_ERROR => Self::FramingError,
+            SMC_RESULT_BAD_ARGUMENT => Self::BadArgument,
+            SMC_RESULT_TIMEOUT => Self::Timeout,
+            SMC_RESULT_KEY_INDEX_RANGE_ERROR => Self::KeyIndexRange,
+            SMC_RESULT_BAD_FUNC_PARAMETER => Self::BadFuncParameter,
+            SMC_RESULT_EVENT_BUFF_WRONG_ORDER => Self::EventBufferWrongOrder,
+            SMC_RESULT_EVENT_BUFF_READ_ERROR => Self::EventBufferReadError,
+            SMC_RESULT_DEVICE_ACCESS_ERROR => Self::DeviceAccessError,
+            SMC_RESULT_UNSUPPORTED_FEATURE => Self::UnsupportedFeature,
+            SMC_RESULT_SMB_ACCESS_ERROR => Self::SmbAccessError,
+            SMC_RESULT_INVALID_SIZE => Self::InvalidSize,
+            code => Self::Unknown(code),
+        }
+    }
+
+    /// Returns the `result` byte.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Error => SMC_RESULT_ERROR,
+            Self::CommCollision => SMC_RESULT_COMM_COLLISION,
+            Self::SpuriousData => SMC_RESULT_SPURIOUS_DATA,
+            Self::BadCommand => SMC_RESULT_BAD_COMMAND,
+            Self::BadParameter => SMC_RESULT_BAD_PARAMETER,
+            Self::KeyNotFound => SMC_RESULT_KEY_NOT_FOUND,
+            Self::KeyNotReadable => SMC_RESULT_NOT_READABLE,
+            Self::KeyNotWritable => SMC_RESULT_NOT_WRITABLE,
+            Self::KeySizeMismatch => SMC_RESULT_KEY_SIZE_MISMATCH,
+            Self::FramingError => SMC_RESULT_FRAMING_ERROR,
+            Self::BadArgument => SMC_RESULT_BAD_ARGUMENT,
+            Self::Timeout => SMC_RESULT_TIMEOUT,
+            Self::KeyIndexRange => SMC_RESULT_KEY_INDEX_RANGE_ERROR,
+            Self::BadFuncParameter => SMC_RESULT_BAD_FUNC_PARAMETER,
+            Self::EventBufferWrongOrder => SMC_RESULT_EVENT_BUFF_WRONG_ORDER,
+            Self::EventBufferReadError => SMC_RESULT_EVENT_BUFF_READ_ERROR,
+            Self::DeviceAccessError => SMC_RESULT_DEVICE_ACCESS_ERROR,
+            Self::UnsupportedFeature => SMC_RESULT_UNSUPPORTED_FEATURE,
+            Self::SmbAccessError => SMC_RESULT_SMB_ACCESS_ERROR,
+            Self::InvalidSize => SMC_RESULT_INVALID_SIZE,
+            Self::Unknown(code) => *code,
+        }
+    }
+}
+
+impl std::fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Self::Error => "error",
+            Self::CommCollision => "communication collision",
+            Self::SpuriousData => "spurious data",
+            Self::BadCommand => "bad command",
+            Self::BadParameter => "bad parameter",
+            Self::KeyNotFound => "key not found",
+            Self::KeyNotReadable => "key not readable",
+            Self::KeyNotWritable => "key not writable",
+            Self::KeySizeMismatch => "key size mismatch",
+            Self::FramingError => "framing error",
+            Self::BadArgument => "bad argument",
+            Self::Timeout => "timeout",
+            Self::KeyIndexRange => "key index out of range",
+            Self::BadFuncParameter => "bad function parameter",
+            Self::EventBufferWrongOrder => "event buffer read in wrong order",
+            Self::EventBufferReadError => "event buffer read error",
+            Self::DeviceAccessError => "device access error",
+            Self::UnsupportedFeature => "unsupported feature",
+            Self::SmbAccessError => "SMBus access error",
+            Self::InvalidSize => "invalid size",
+            Self::Unknown(_) => "unknown error",
+        };
+        write!(f, "{} (SMC result {:#04x})", msg, self.code())
+    }
+}
+
+impl std::fmt::Display for SmcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmcError::KeyNotFound => write!(f, "key not found"),
+            SmcError::SizeMismatch { expected, actual } => write!(
+                f,
+                "size mismatch, the key has {} bytes, got {}",
+                expected, actual
+            ),
+            SmcError::ValueTooLong(len) => write!(
+                f,
+                "value is too long, {} bytes, at most {}",
+                len, SMC_BYTES_LEN
+            ),
+            SmcError::PrivilegeViolation => write!(f, "privilege violation"),
+            SmcError::ServiceNotFound => write!(f, "AppleSMC service not found"),
+            SmcError::OpenFailed(code) => {
+                write!(f, "can not open AppleSMC service: {}", err_str(*code))
+            }
+            SmcError::Transport(code) => write!(f, "{} ({:#010x})", err_str(*code), code),
+            SmcError::Firmware(err) => write!(f, "{}", err),
+            SmcError::Encode(err) => write!(f, "{}", err),
+            SmcError::UnexpectedType { key, data_type } => {
+                write!(f, "key {} has the unexpected data type {}", key, data_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SmcError {}
+
 /// Handle of the Apple SMC.
 ///
 /// This struct manages the connection to the SMC and provides methods to
 /// read, write, and enumerate SMC keys.
 ///
+/// The SMC is reached through a [`SmcTransport`]. On macOS it defaults to
+/// the IOKit connection opened by [`IOService::init`], any other transport
+/// can be used through [`IOService::with_transport`].
+///
 /// # Example
 ///
 /// ```no_run
+/// # #[cfg(target_os = "macos")] {
 /// use smc_lib::io::IOService;
-/// use smc_lib::io::err_str;
 ///
 /// fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     // Initialize SMC connection
@@ -33,24 +275,43 @@ unsafe extern "C" {
 ///
 ///     // Read a key (e.g. battery temperature)
 ///     let key = b"TB0T";
-///     let value = smc.read_key(key).map_err(err_str)?;
+///     let value = smc.read_key(key)?;
 ///     println!("{}", value);
 ///
 ///     // Get key information
-///     let key_info = smc.get_key_info(key).map_err(err_str)?;
+///     let key_info = smc.get_key_info(key)?;
 ///     println!(
 ///         "data type: {}, size: {}",
-///         String::from_utf8_lossy(&key_info.data_type.to_be_bytes()).trim(),
+///         key_info.data_type().to_string().trim(),
 ///         key_info.data_size
 ///     );
 ///
 ///     Ok(())
 ///  }
+/// # }
 /// ```
-pub struct IOService {
-    conn: io_connect_t,
+#[cfg(target_os = "macos")]
+pub struct IOService<T = crate::iokit::IOKitConnection> {
+    transport: T,
+    byte_order: ByteOrder,
+    key_byte_order: HashMap<SmcKey, ByteOrder>,
+    key_info_cache: Option<Mutex<KeyInfoCache>>,
+}
+
+/// Handle of the Apple SMC.
+///
+/// This struct provides methods to read, write, and enumerate SMC keys
+/// through a [`SmcTransport`], see [`IOService::with_transport`].
+#[cfg(not(target_os = "macos"))]
+pub struct IOService<T> {
+    transport: T,
+    byte_order: ByteOrder,
+    key_byte_order: HashMap<SmcKey, ByteOrder>,
+    key_info_cache: Option<Mutex<KeyInfoCache>>,
 }
 
+type KeyInfoCache = HashMap<SmcKey, SMCKeyData_keyInfo>;
+
 /// Converts a kernel error code to a human-readable string.
 ///
 /// # Arguments
@@ -64,111 +325,254 @@ pub struct IOService {
 /// # Example
 ///
 /// ```
-/// use smc_lib::io::err_str;
-/// use libc::KERN_FAILURE;
+/// use smc_lib::io::{KERN_FAILURE, err_str};
 ///
 /// let error_msg = err_str(KERN_FAILURE);
 /// println!("Error: {}", error_msg);
 /// ```
-pub fn err_str(error_value: libc::kern_return_t) -> Cow<'static, str> {
-    unsafe { CStr::from_ptr(mach_error_string(error_value)).to_string_lossy() }
+#[cfg(target_os = "macos")]
+pub fn err_str(error_value: kern_return_t) -> Cow<'static, str> {
+    unsafe { std::ffi::CStr::from_ptr(libc::mach_error_string(error_value)).to_string_lossy() }
 }
 
-impl IOService {
-    /// Initializes a connection to the Apple SMC.
-    ///
-    /// This function opens the IOKit connection to the AppleSMC service
-    /// and returns a handle that can be used to interact with SMC keys.
-    ///
-    /// # Returns
-    ///
-    /// - `Ok(IOService)` - A handle to the SMC service
-    /// - `Err(String)` - An error message if initialization fails
+/// Converts a kernel error code to a human-readable string.
+///
+/// Only the codes produced by this crate are known, other codes are
+/// printed as hex.
+///
+/// # Example
+///
+/// ```
+/// use smc_lib::io::{KERN_FAILURE, err_str};
+///
+/// let error_msg = err_str(KERN_FAILURE);
+/// println!("Error: {}", error_msg);
+/// ```
+#[cfg(not(target_os = "macos"))]
+pub fn err_str(error_value: kern_return_t) -> Cow<'static, str> {
+    // same text as `mach_error_string` on macOS
+    match error_value {
+        KERN_SUCCESS => "(os/kern) successful".into(),
+        KERN_INVALID_ARGUMENT => "(os/kern) invalid argument".into(),
+        KERN_FAILURE => "(os/kern) failure".into(),
+        KERN_NOT_SUPPORTED => "(os/kern) not supported".into(),
+        IO_RETURN_NOT_PRIVILEGED => "(iokit/common) privilege violation".into(),
+        IO_RETURN_BAD_ARGUMENT => "(iokit/common) invalid argument".into(),
+        code => format!("unknown error code {:#010x}", code).into(),
+    }
+}
+
+impl<T: SmcTransport> IOService<T> {
+    /// Creates a handle that talks to a SMC through `transport`.
     ///
-    /// # Errors
+    /// The byte order is the one reported by [`SmcTransport::byte_order`].
+    pub fn with_transport(transport: T) -> Self {
+        Self {
+            byte_order: transport.byte_order(),
+            transport,
+            key_byte_order: HashMap::new(),
+            key_info_cache: None,
+        }
+    }
+
+    /// Replaces the transport, keeping the configuration of the handle.
+    pub(crate) fn map_transport<U>(self, f: impl FnOnce(T) -> U) -> IOService<U> {
+        IOService {
+            transport: f(self.transport),
+            byte_order: self.byte_order,
+            key_byte_order: self.key_byte_order,
+            key_info_cache: self.key_info_cache,
+        }
+    }
+
+    /// Enables the key info cache.
     ///
-    /// This function will return an error if:
-    /// - The IO main port cannot be initialized
-    /// - The AppleSMC service cannot be found
-    /// - The IOService cannot be opened
+    /// The data type and size of a key never change at runtime, with the
+    /// cache the key info is only read the first time a key is used, so
+    /// [`read_key`](Self::read_key) takes one SMC call instead of two.
+    /// [`cache_all_key_info`](Self::cache_all_key_info) fills the cache
+    /// up front.
     ///
     /// # Example
     ///
-    /// ```no_run
+    /// ```
     /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
     ///
-    /// let smc = IOService::init().unwrap();
-    /// println!("Successfully connected to SMC");
+    /// let smc = IOService::with_transport(MockSmc::sample()).with_key_info_cache();
+    /// // reads the key info and the value
+    /// smc.read_key(b"TB0T").unwrap();
+    /// // only reads the value
+    /// smc.read_key(b"TB0T").unwrap();
     /// ```
-    pub fn init() -> Result<Self, Cow<'static, str>> {
-        unsafe {
-            let mut main_port = 0;
-            let res = IOMainPort(0, &raw mut main_port);
-            if res != KERN_SUCCESS {
-                return Err(
-                    format!("Can not initialize IO main port, error: {}", err_str(res)).into(),
-                );
-            }
-            let matching_dict =
-                IOServiceMatching(c"AppleSMC".as_ptr()).and_then(|d| d.downcast().ok());
-            let mut iterator = 0;
-            let res = IOServiceGetMatchingServices(main_port, matching_dict, &raw mut iterator);
-            if res != KERN_SUCCESS {
-                return Err(format!(
-                    "Can not get matching service AppleSMC, error: {}",
-                    err_str(res)
-                )
-                .into());
-            }
-            let device = IOIteratorNext(iterator);
-            IOObjectRelease(iterator);
-            if device == 0 {
-                return Err("No SMC found".into());
-            }
-            let mut conn = 0;
-            let res = IOServiceOpen(device, mach_task_self(), 0, &raw mut conn);
-            IOObjectRelease(device);
-            if res != KERN_SUCCESS {
-                return Err(
-                    format!("IOServiceOpen() = {:08x}, error: {}", res, err_str(res)).into(),
-                );
-            }
-            Ok(Self { conn })
+    pub fn with_key_info_cache(mut self) -> Self {
+        self.key_info_cache = Some(Mutex::new(HashMap::new()));
+        self
+    }
+
+    /// Reads the key info of every key into the cache.
+    ///
+    /// Keys whose key info can not be read are skipped. Does nothing if the
+    /// cache is not enabled, see [`with_key_info_cache`](Self::with_key_info_cache).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keys can not be enumerated.
+    pub fn cache_all_key_info(&self) -> Result<(), SmcError> {
+        if self.key_info_cache.is_none() {
+            return Ok(());
         }
+        for i in 0..self.keys_count()? {
+            let input_struct = SMCKeyData {
+                data8: SMC_CMD_READ_INDEX,
+                data32: i,
+                ..Default::default()
+            };
+            let mut output_struct = SMCKeyData::default();
+            self.smc_call(&input_struct, &mut output_struct)?;
+            // skip keys that can't be read
+            let _ = self.cached_key_info(SmcKey::from(output_struct.key));
+        }
+        Ok(())
     }
 
-    /// `input_struct` should set key
-    fn get_key_info_inner(
+    /// Removes the key info of `key` from the cache.
+    pub fn invalidate_key_info(&self, key: impl Into<SmcKey>) {
+        if let Some(mut cache) = self.lock_key_info_cache() {
+            cache.remove(&key.into());
+        }
+    }
+
+    /// Removes every key info from the cache.
+    pub fn clear_key_info_cache(&self) {
+        if let Some(mut cache) = self.lock_key_info_cache() {
+            cache.clear();
+        }
+    }
+
+    fn lock_key_info_cache(&self) -> Option<MutexGuard<'_, KeyInfoCache>> {
+        let cache = self.key_info_cache.as_ref()?;
+        Some(cache.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Returns the key info of `key`, from the cache if it is enabled.
+    fn cached_key_info(&self, key: SmcKey) -> Result<SMCKeyData_keyInfo, SmcError> {
+        let mut input_struct = SMCKeyData::default();
+        let mut output_struct = SMCKeyData::default();
+        self.cached_key_info_with(key, &mut input_struct, &mut output_struct)
+    }
+
+    /// Like `cached_key_info`, with the buffers of the SMC call.
+    fn cached_key_info_with(
         &self,
+        key: SmcKey,
         input_struct: &mut SMCKeyData,
         output_struct: &mut SMCKeyData,
-    ) -> Result<(), libc::kern_return_t> {
-        input_struct.data8 = SMC_CMD_READ_KEYINFO;
-        self.smc_call(KERNEL_INDEX_SMC, input_struct, output_struct)?;
-        if output_struct.result == 132 {
-            return Err(libc::KERN_NOT_SUPPORTED);
+    ) -> Result<SMCKeyData_keyInfo, SmcError> {
+        if let Some(info) = self
+            .lock_key_info_cache()
+            .and_then(|cache| cache.get(&key).copied())
+        {
+            return Ok(info);
         }
-        Ok(())
+        *input_struct = SMCKeyData {
+            key: key.to_u32(),
+            ..Default::default()
+        };
+        self.get_key_info_inner(input_struct, output_struct)?;
+        if let Some(mut cache) = self.lock_key_info_cache() {
+            cache.insert(key, output_struct.key_info);
+        }
+        Ok(output_struct.key_info)
     }
 
-    /// you need to call `get_key_info_inner` first
+    /// Reads the value of `key`, whose key info is `info`.
     fn read_key_with_info(
         &self,
+        key: SmcKey,
+        info: &SMCKeyData_keyInfo,
         input_struct: &mut SMCKeyData,
         output_struct: &mut SMCKeyData,
-    ) -> Result<SMCVal, libc::kern_return_t> {
-        let mut val = SMCVal {
-            key: input_struct.key.to_be_bytes(),
-            data_size: output_struct.key_info.data_size,
-            data_type: output_struct.key_info.data_type.to_be_bytes(),
+    ) -> Result<SMCVal, SmcError> {
+        *input_struct = SMCKeyData {
+            key: key.to_u32(),
+            data8: SMC_CMD_READ_BYTES,
+            key_info: SMCKeyData_keyInfo {
+                data_size: info.data_size,
+                ..Default::default()
+            },
             ..Default::default()
         };
-        input_struct.key_info.data_size = output_struct.key_info.data_size;
-        input_struct.key_info.data_type = output_struct.key_info.data_type;
-        input_struct.data8 = SMC_CMD_READ_BYTES;
-        self.smc_call(KERNEL_INDEX_SMC, input_struct, output_struct)?;
-        val.bytes = output_struct.bytes;
-        Ok(val)
+        if let Err(err) = self.smc_call(input_struct, output_struct) {
+            // the cached size may be stale
+            if err == SmcError::Firmware(FirmwareError::KeySizeMismatch) {
+                self.invalidate_key_info(key);
+            }
+            return Err(err);
+        }
+        Ok(SMCVal {
+            key,
+            data_size: info.data_size,
+            data_type: info.data_type(),
+            attributes: info.attributes(),
+            bytes: output_struct.bytes,
+            byte_order: self.byte_order_of(key),
+        })
+    }
+
+    /// Sets the byte order used to decode and encode values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
+    /// use smc_lib::value::{ByteOrder, SmcValue};
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample()).with_byte_order(ByteOrder::Big);
+    /// let val = smc.read_key(b"B0CT").unwrap();
+    /// assert_eq!(val.data_value(), Some(SmcValue::U16(0x7b00)));
+    /// assert_eq!(val.data_value_in(ByteOrder::Little), Some(SmcValue::U16(123)));
+    /// ```
+    pub fn with_byte_order(mut self, order: ByteOrder) -> Self {
+        self.byte_order = order;
+        self
+    }
+
+    /// Sets the byte order of a single key, for keys that do not follow the
+    /// byte order of the SMC.
+    pub fn with_key_byte_order(mut self, key: impl Into<SmcKey>, order: ByteOrder) -> Self {
+        self.key_byte_order.insert(key.into(), order);
+        self
+    }
+
+    /// Returns the byte order of the SMC.
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// Returns the byte order of `key`.
+    pub fn byte_order_of(&self, key: impl Into<SmcKey>) -> ByteOrder {
+        let key = key.into();
+        self.key_byte_order
+            .get(&key)
+            .copied()
+            .unwrap_or(self.byte_order)
+    }
+
+    /// Returns the transport used by this handle.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// `input_struct` should set key
+    fn get_key_info_inner(
+        &self,
+        input_struct: &mut SMCKeyData,
+        output_struct: &mut SMCKeyData,
+    ) -> Result<(), SmcError> {
+        input_struct.data8 = SMC_CMD_READ_KEYINFO;
+        self.smc_call(input_struct, output_struct)
     }
 
     /// Retrieves metadata about a SMC key.
@@ -178,53 +582,38 @@ impl IOService {
     ///
     /// # Arguments
     ///
-    /// - `key` - A 4-byte array representing the SMC key name (e.g., `b"TB0T"`)
+    /// - `key` - The SMC key name, a [`SmcKey`] or a 4-byte array (e.g., `b"TB0T"`)
     ///
     /// # Returns
     ///
     /// - `Ok(SMCKeyData_keyInfo)` - Metadata about the key
-    /// - `Err(kern_return_t)` - Kernel error code if the operation fails
+    /// - `Err(SmcError)` - The error if the operation fails
     ///
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let info = smc.get_key_info(b"TB0T").unwrap();
     /// println!("Data type: {}, Size: {}", info.data_type, info.data_size);
+    /// # }
     /// ```
-    pub fn get_key_info(&self, key: &[u8; 4]) -> Result<SMCKeyData_keyInfo, libc::kern_return_t> {
-        let mut input_struct = SMCKeyData {
-            key: u32::from_be_bytes(*key),
-            ..Default::default()
-        };
-        let mut output_struct = SMCKeyData::default();
-        self.get_key_info_inner(&mut input_struct, &mut output_struct)?;
-        Ok(output_struct.key_info)
+    pub fn get_key_info(&self, key: impl Into<SmcKey>) -> Result<SMCKeyData_keyInfo, SmcError> {
+        self.cached_key_info(key.into())
     }
 
     fn smc_call(
         &self,
-        selector: u32,
         input_struct: &SMCKeyData,
         output_struct: &mut SMCKeyData,
-    ) -> Result<(), libc::kern_return_t> {
-        unsafe {
-            let mut output_struct_cnt = size_of::<SMCKeyData>();
-            let res = IOConnectCallStructMethod(
-                self.conn,
-                selector,
-                input_struct as *const _ as *const c_void,
-                size_of::<SMCKeyData>(),
-                output_struct as *mut _ as *mut c_void,
-                &raw mut output_struct_cnt,
-            );
-            if res == KERN_SUCCESS {
-                Ok(())
-            } else {
-                Err(res)
-            }
+    ) -> Result<(), SmcError> {
+        self.transport.call(input_struct, output_struct)?;
+        // the call went through, but the firmware may still reject the command
+        match SmcError::from_result(output_struct.result) {
+            Some(err) => Err(err),
+            None => Ok(()),
         }
     }
 
@@ -234,54 +623,91 @@ impl IOService {
     ///
     /// # Arguments
     ///
-    /// - `key` - A 4-byte array representing the SMC key name (e.g., `b"TB0T"` for battery temperature)
+    /// - `key` - The SMC key name, a [`SmcKey`] or a 4-byte array (e.g., `b"TB0T"` for battery temperature)
     ///
     /// # Returns
     ///
     /// - `Ok(SMCVal)` - The SMC value containing key name, data type, size, and raw bytes
-    /// - `Err(kern_return_t)` - Kernel error code if the read operation fails
+    /// - `Err(SmcError)` - The error if the read operation fails
     ///
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// // Read battery temperature
     /// let temp = smc.read_key(b"TB0T").unwrap();
     /// println!("{}", temp);
+    /// # }
     /// ```
-    pub fn read_key(&self, key: &[u8; 4]) -> Result<SMCVal, libc::kern_return_t> {
-        let mut input_struct = SMCKeyData {
-            key: u32::from_be_bytes(*key),
-            ..Default::default()
-        };
+    pub fn read_key(&self, key: impl Into<SmcKey>) -> Result<SMCVal, SmcError> {
+        let key = key.into();
+        let mut input_struct = SMCKeyData::default();
         let mut output_struct = SMCKeyData::default();
-        self.get_key_info_inner(&mut input_struct, &mut output_struct)?;
-        let mut val = SMCVal {
-            key: *key,
-            data_size: output_struct.key_info.data_size,
-            data_type: output_struct.key_info.data_type.to_be_bytes(),
-            ..Default::default()
-        };
-        input_struct.key_info.data_size = output_struct.key_info.data_size;
-        input_struct.data8 = SMC_CMD_READ_BYTES;
-        self.smc_call(KERNEL_INDEX_SMC, &input_struct, &mut output_struct)?;
-        val.bytes = output_struct.bytes;
-        Ok(val)
+        let info = self.cached_key_info_with(key, &mut input_struct, &mut output_struct)?;
+        self.read_key_with_info(key, &info, &mut input_struct, &mut output_struct)
+    }
+
+    /// Reads the values of many SMC keys.
+    ///
+    /// Returns one result per key, in the order of `keys`. A key that can
+    /// not be read does not stop the batch. The key info cache is used if it
+    /// is enabled, see [`with_key_info_cache`](Self::with_key_info_cache).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::{IOService, SmcError};
+    /// use smc_lib::key::SmcKey;
+    /// use smc_lib::mock::MockSmc;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// let keys = [SmcKey::new(b"TB0T"), SmcKey::new(b"XXXX")];
+    /// let values = smc.read_many(&keys);
+    /// assert!(values[0].is_ok());
+    /// assert_eq!(values[1].map(|_| ()), Err(SmcError::KeyNotFound));
+    /// ```
+    pub fn read_many(&self, keys: &[SmcKey]) -> Vec<Result<SMCVal, SmcError>> {
+        let mut input_struct = SMCKeyData::default();
+        let mut output_struct = SMCKeyData::default();
+        keys.iter()
+            .map(|&key| {
+                let info = self.cached_key_info_with(key, &mut input_struct, &mut output_struct)?;
+                self.read_key_with_info(key, &info, &mut input_struct, &mut output_struct)
+            })
+            .collect()
+    }
+
+    /// Reads a value, `None` if the key does not exist or can not be decoded.
+    pub(crate) fn read_optional(
+        &self,
+        key: impl Into<SmcKey>,
+    ) -> Result<Option<SmcValue>, SmcError> {
+        match self.read_key(key) {
+            Ok(val) => Ok(val.data_value()),
+            Err(SmcError::KeyNotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a numeric value, see [`SmcValue::as_f64`].
+    pub(crate) fn read_number(&self, key: impl Into<SmcKey>) -> Result<Option<f64>, SmcError> {
+        Ok(self.read_optional(key)?.and_then(|v| v.as_f64()))
     }
 
     /// Writes a value to a SMC key, this usually need root privilege
     ///
     /// # Arguments
     ///
-    /// - `key` - A 4-byte array representing the SMC key name
+    /// - `key` - The SMC key name, a [`SmcKey`] or a 4-byte array
     /// - `value` - The byte array to write. Must match the expected size of the key's data
     ///
     /// # Returns
     ///
     /// - `Ok(())` - If the write was successful
-    /// - `Err(kern_return_t)` - Kernel error code if the operation fails
+    /// - `Err(SmcError)` - The error if the operation fails
     ///
     /// # Errors
     ///
@@ -298,6 +724,7 @@ impl IOService {
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
@@ -305,22 +732,27 @@ impl IOService {
     /// let value = [0x03];
     /// // this make the MagSafe light turn green
     /// smc.write_key(b"ACLC", &value).unwrap();
+    /// # }
     /// ```
-    pub fn write_key(&self, key: &[u8; 4], value: &[u8]) -> Result<(), libc::kern_return_t> {
+    pub fn write_key(&self, key: impl Into<SmcKey>, value: &[u8]) -> Result<(), SmcError> {
+        let key = key.into();
         let val_len = value.len();
         if val_len > SMC_BYTES_LEN {
-            return Err(libc::KERN_INVALID_ARGUMENT);
+            return Err(SmcError::ValueTooLong(val_len));
         }
         let mut write_bytes = SMCBytes::default();
         write_bytes[..val_len].copy_from_slice(value);
 
-        let read_val = self.read_key(key)?;
-        if read_val.data_size != val_len as u32 {
-            return Err(libc::KERN_INVALID_ARGUMENT);
+        let info = self.cached_key_info(key)?;
+        if info.data_size != val_len as u32 {
+            return Err(SmcError::SizeMismatch {
+                expected: info.data_size,
+                actual: val_len as u32,
+            });
         }
 
         let input_struct = SMCKeyData {
-            key: u32::from_be_bytes(*key),
+            key: key.to_u32(),
             data8: SMC_CMD_WRITE_BYTES,
             key_info: SMCKeyData_keyInfo {
                 data_size: val_len as u32,
@@ -330,10 +762,56 @@ impl IOService {
             ..Default::default()
         };
         let mut output_struct = SMCKeyData::default();
-        self.smc_call(KERNEL_INDEX_SMC, &input_struct, &mut output_struct)?;
+        self.smc_call(&input_struct, &mut output_struct)?;
         Ok(())
     }
 
+    /// Writes a typed value to a SMC key, this usually need root privilege
+    ///
+    /// The data type of the key is looked up and `value` is encoded with
+    /// [`SmcValue::encode`], in the byte order of the key, see
+    /// [`byte_order_of`](Self::byte_order_of). Values of variable sized types such as `ch8*`
+    /// are padded with zeros to the size of the key.
+    ///
+    /// # Arguments
+    ///
+    /// - `key` - The SMC key name, a [`SmcKey`] or a 4-byte array
+    /// - `value` - A [`SmcValue`], or a primitive such as `u8`, `f32` or `bool`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SmcError::Encode`] if `value` does not fit the data type of
+    /// the key, and the errors of [`IOService::write_key`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
+    /// use smc_lib::io::IOService;
+    ///
+    /// let smc = IOService::init().unwrap();
+    /// // this make the MagSafe light turn green
+    /// smc.write_value(b"ACLC", 3u8).unwrap();
+    /// # }
+    /// ```
+    pub fn write_value(
+        &self,
+        key: impl Into<SmcKey>,
+        value: impl Into<SmcValue>,
+    ) -> Result<(), SmcError> {
+        let key = key.into();
+        let info = self.get_key_info(key)?;
+        let data_type = info.data_type();
+        let mut bytes = value
+            .into()
+            .encode(data_type, self.byte_order_of(key))
+            .map_err(SmcError::Encode)?;
+        if data_type.size().is_none() && bytes.len() < info.data_size as usize {
+            bytes.resize(info.data_size as usize, 0);
+        }
+        self.write_key(key, &bytes)
+    }
+
     /// Returns the total number of SMC keys available on the system.
     ///
     /// This queries the special `#KEY` SMC key which contains the count of all keys.
@@ -341,25 +819,30 @@ impl IOService {
     /// # Returns
     ///
     /// - `Ok(u32)` - The number of available SMC keys
-    /// - `Err(kern_return_t)` - Kernel error code if the operation fails
+    /// - `Err(SmcError)` - The error if the operation fails
     ///
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let count = smc.keys_count().unwrap();
     /// println!("Total SMC keys: {}", count);
+    /// # }
     /// ```
-    pub fn keys_count(&self) -> Result<u32, libc::kern_return_t> {
+    pub fn keys_count(&self) -> Result<u32, SmcError> {
         let val = self.read_key(b"#KEY")?;
         if val.data_size == 4 {
             let mut bytes = [0; 4];
             bytes.copy_from_slice(&val.bytes[..4]);
             Ok(u32::from_be_bytes(bytes))
         } else {
-            Err(libc::KERN_FAILURE)
+            Err(SmcError::SizeMismatch {
+                expected: 4,
+                actual: val.data_size,
+            })
         }
     }
 
@@ -371,7 +854,7 @@ impl IOService {
     /// # Returns
     ///
     /// - `Ok(Vec<SMCVal>)` - A vector containing all readable SMC values
-    /// - `Err(kern_return_t)` - Kernel error code if enumeration fails
+    /// - `Err(SmcError)` - The error if enumeration fails
     ///
     /// # Note
     ///
@@ -381,13 +864,15 @@ impl IOService {
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let all_values = smc.list_all_values().unwrap();
     /// println!("all values: {:?}", all_values);
+    /// # }
     /// ```
-    pub fn list_all_values(&self) -> Result<Vec<SMCVal>, libc::kern_return_t> {
+    pub fn list_all_values(&self) -> Result<Vec<SMCVal>, SmcError> {
         let total_count = self.keys_count()?;
         let mut values = Vec::with_capacity(total_count as usize);
         for i in 0..total_count {
@@ -397,9 +882,9 @@ impl IOService {
                 ..Default::default()
             };
             let mut output_struct = SMCKeyData::default();
-            self.smc_call(KERNEL_INDEX_SMC, &input_struct, &mut output_struct)?;
+            self.smc_call(&input_struct, &mut output_struct)?;
             // skip values that can't be read
-            let Ok(val) = self.read_key(&output_struct.key.to_be_bytes()) else {
+            let Ok(val) = self.read_key(output_struct.key) else {
                 continue;
             };
             values.push(val);
@@ -415,11 +900,12 @@ impl IOService {
     /// # Returns
     ///
     /// - `Ok(ValIter)` - An iterator over SMC values
-    /// - `Err(kern_return_t)` - Kernel error code if initialization fails
+    /// - `Err(SmcError)` - The error if initialization fails
     ///
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
@@ -430,21 +916,140 @@ impl IOService {
     ///         Err(e) => eprintln!("{}", e),
     ///     }
     /// }
+    /// # }
     /// ```
-    pub fn values_iter(&self) -> Result<ValIter<'_>, libc::kern_return_t> {
+    pub fn values_iter(&self) -> Result<ValIter<'_, T>, SmcError> {
         let total_count = self.keys_count()?;
-        let val_iter = ValIter {
+        Ok(ValIter::range(self, 0, total_count))
+    }
+
+    /// Returns an iterator over all SMC keys and their key info, without
+    /// reading the values.
+    ///
+    /// Each key takes two SMC calls, or one with
+    /// [`KeyIter::without_info`] or a cached key info. Skipped keys
+    /// take no call, so `skip(n)` and `nth(n)` are cheap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of keys can not be read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// let keys = smc.keys_iter().unwrap().without_info();
+    /// assert_eq!(keys.len(), 8);
+    /// for entry in keys.skip(6) {
+    ///     let entry = entry.unwrap();
+    ///     println!("{} {}", entry.index, entry.key);
+    /// }
+    /// ```
+    pub fn keys_iter(&self) -> Result<KeyIter<'_, T>, SmcError> {
+        let end = self.keys_count()?;
+        Ok(KeyIter {
             service: self,
-            total_count,
+            end,
             current: 0,
+            with_info: true,
+        })
+    }
+
+    /// Returns the key at `index` in the SMC key table.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::key::SmcKey;
+    /// use smc_lib::mock::MockSmc;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// assert_eq!(smc.key_at_index(0), Ok(SmcKey::new(b"#KEY")));
+    /// assert_eq!(smc.index_of(b"TB0T"), Ok(7));
+    /// ```
+    pub fn key_at_index(&self, index: u32) -> Result<SmcKey, SmcError> {
+        let input_struct = SMCKeyData {
+            data8: SMC_CMD_READ_INDEX,
+            data32: index,
+            ..Default::default()
         };
-        Ok(val_iter)
+        let mut output_struct = SMCKeyData::default();
+        self.smc_call(&input_struct, &mut output_struct)?;
+        Ok(SmcKey::from(output_struct.key))
+    }
+
+    /// Returns the index of `key` in the SMC key table.
+    ///
+    /// The table is sorted, so this is a binary search taking about
+    /// `log2(keys_count)` SMC calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SmcError::KeyNotFound`] if the key does not exist.
+    pub fn index_of(&self, key: impl Into<SmcKey>) -> Result<u32, SmcError> {
+        let key = key.into();
+        let count = self.keys_count()?;
+        let index = self.partition_point(count, |k| k < key)?;
+        if index < count && self.key_at_index(index)? == key {
+            Ok(index)
+        } else {
+            Err(SmcError::KeyNotFound)
+        }
+    }
+
+    /// Returns an iterator over the keys from `first` to `last`, both
+    /// included, see [`keys_iter`](Self::keys_iter).
+    ///
+    /// The range is found by binary search, so listing `k` keys takes about
+    /// `2 * log2(keys_count) + k` SMC calls instead of a scan of every key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// // temperature sensors
+    /// for entry in smc.keys_between(b"T000", b"TZZZ").unwrap() {
+    ///     let entry = entry.unwrap();
+    ///     println!("{} {:?}", entry.key, entry.info);
+    /// }
+    /// ```
+    pub fn keys_between(
+        &self,
+        first: impl Into<SmcKey>,
+        last: impl Into<SmcKey>,
+    ) -> Result<KeyIter<'_, T>, SmcError> {
+        let (first, last) = (first.into(), last.into());
+        let count = self.keys_count()?;
+        let start = self.partition_point(count, |k| k < first)?;
+        let end = self.partition_point(count, |k| k <= last)?;
+        Ok(KeyIter {
+            service: self,
+            end: end.max(start),
+            current: start,
+            with_info: true,
+        })
     }
-}
 
-impl Drop for IOService {
-    fn drop(&mut self) {
-        IOServiceClose(self.conn);
+    /// Returns the first index in `0..count` whose key does not match `pred`,
+    /// the keys matching `pred` must come first.
+    fn partition_point(&self, count: u32, pred: impl Fn(SmcKey) -> bool) -> Result<u32, SmcError> {
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if pred(self.key_at_index(mid)?) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
     }
 }
 
@@ -453,28 +1058,126 @@ impl Drop for IOService {
 /// This iterator is created by [`IOService::values_iter`] and yields
 /// `Result<SMCVal, ValError>` for each key in the SMC.
 ///
-pub struct ValIter<'a> {
-    service: &'a IOService,
+pub struct ValIter<'a, T> {
+    service: &'a IOService<T>,
     total_count: u32,
     current: u32,
 }
 
+impl<'a, T> ValIter<'a, T> {
+    /// Iterates over the keys from index `current` to `total_count`.
+    pub(crate) fn range(service: &'a IOService<T>, current: u32, total_count: u32) -> Self {
+        Self {
+            service,
+            total_count,
+            current,
+        }
+    }
+}
+
+/// Iterator over SMC keys.
+///
+/// This iterator is created by [`IOService::keys_iter`] and
+/// [`IOService::keys_between`], and yields `Result<KeyEntry, ValError>` for
+/// each key.
+pub struct KeyIter<'a, T> {
+    service: &'a IOService<T>,
+    end: u32,
+    current: u32,
+    with_info: bool,
+}
+
+/// A key yielded by [`KeyIter`].
+#[derive(Clone, Copy, Debug)]
+pub struct KeyEntry {
+    /// Index of the key in the SMC key table
+    pub index: u32,
+    pub key: SmcKey,
+    /// `None` if the iterator was created [`without_info`](KeyIter::without_info)
+    pub info: Option<SMCKeyData_keyInfo>,
+}
+
+impl<T> KeyIter<'_, T> {
+    /// Skips the key info call, only the names of the keys are read.
+    pub fn without_info(mut self) -> Self {
+        self.with_info = false;
+        self
+    }
+}
+
+impl<T: SmcTransport> Iterator for KeyIter<'_, T> {
+    type Item = Result<KeyEntry, ValError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current >= self.end {
+            return None;
+        }
+        let index = self.current;
+        self.current += 1;
+        let key = match self.service.key_at_index(index) {
+            Ok(key) => key,
+            Err(error) => {
+                let err = ValError {
+                    error,
+                    index,
+                    key: None,
+                    data_size: None,
+                    data_type: None,
+                };
+                return Some(Err(err));
+            }
+        };
+        let info = if self.with_info {
+            match self.service.cached_key_info(key) {
+                Ok(info) => Some(info),
+                Err(error) => {
+                    let err = ValError {
+                        error,
+                        index,
+                        key: Some(key),
+                        data_size: None,
+                        data_type: None,
+                    };
+                    return Some(Err(err));
+                }
+            }
+        } else {
+            None
+        };
+        Some(Ok(KeyEntry { index, key, info }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.current) as usize;
+        (len, Some(len))
+    }
+
+    /// Skips `n` keys without SMC calls.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.current = self.current.saturating_add(n).min(self.end);
+        self.next()
+    }
+}
+
+impl<T: SmcTransport> ExactSizeIterator for KeyIter<'_, T> {}
+
 /// Error information for failed SMC key operations.
 ///
 /// This struct contains details about errors that occur when iterating
-/// over SMC keys, including the error code, index, and key information.
+/// over SMC keys, including the error, index, and key information.
 ///
 /// This struct implements `Display` trait, so you can print it directly.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ValError {
-    pub err_code: libc::kern_return_t,
+    pub error: SmcError,
     pub index: u32,
-    pub key: Option<u32>,
+    pub key: Option<SmcKey>,
     pub data_size: Option<u32>,
-    pub data_type: Option<u32>,
+    pub data_type: Option<DataType>,
 }
 
-impl Iterator for ValIter<'_> {
+impl<T: SmcTransport> Iterator for ValIter<'_, T> {
     type Item = Result<SMCVal, ValError>;
     fn next(&mut self) -> Option<Self::Item> {
         if self.current < self.total_count {
@@ -486,43 +1189,46 @@ impl Iterator for ValIter<'_> {
                 ..Default::default()
             };
             let mut output_struct = SMCKeyData::default();
-            if let Err(err_code) =
-                self.service
-                    .smc_call(KERNEL_INDEX_SMC, &input_struct, &mut output_struct)
-            {
+            if let Err(error) = self.service.smc_call(&input_struct, &mut output_struct) {
                 let err = ValError {
-                    err_code,
+                    error,
                     index: current,
-                    ..Default::default()
+                    key: None,
+                    data_size: None,
+                    data_type: None,
                 };
                 return Some(Err(err));
             }
-            // set the key
-            input_struct.key = output_struct.key;
-            if let Err(err_code) = self
-                .service
-                .get_key_info_inner(&mut input_struct, &mut output_struct)
-            {
-                let err = ValError {
-                    err_code,
-                    index: current,
-                    key: Some(input_struct.key),
-                    ..Default::default()
+            let key = SmcKey::from(output_struct.key);
+            let info =
+                match self
+                    .service
+                    .cached_key_info_with(key, &mut input_struct, &mut output_struct)
+                {
+                    Ok(info) => info,
+                    Err(error) => {
+                        let err = ValError {
+                            error,
+                            index: current,
+                            key: Some(key),
+                            data_size: None,
+                            data_type: None,
+                        };
+                        return Some(Err(err));
+                    }
                 };
-                return Some(Err(err));
-            }
             match self
                 .service
-                .read_key_with_info(&mut input_struct, &mut output_struct)
+                .read_key_with_info(key, &info, &mut input_struct, &mut output_struct)
             {
                 Ok(v) => Some(Ok(v)),
-                Err(err_code) => {
+                Err(error) => {
                     let err = ValError {
-                        err_code,
+                        error,
                         index: current,
-                        key: Some(input_struct.key),
-                        data_size: Some(input_struct.key_info.data_size),
-                        data_type: Some(input_struct.key_info.data_type),
+                        key: Some(key),
+                        data_size: Some(info.data_size),
+                        data_type: Some(info.data_type()),
                     };
                     Some(Err(err))
                 }
@@ -531,34 +1237,299 @@ impl Iterator for ValIter<'_> {
             None
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.total_count.saturating_sub(self.current) as usize;
+        (len, Some(len))
+    }
+
+    /// Skips `n` keys without SMC calls.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.current = self.current.saturating_add(n).min(self.total_count);
+        self.next()
+    }
 }
 
+impl<T: SmcTransport> ExactSizeIterator for ValIter<'_, T> {}
+
 #[test]
-#[ignore = "the key may not exist"]
-fn basic_example() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize SMC connection
-    let smc = IOService::init()?;
-
-    // Read a key (e.g. battery temperature)
-    let key = b"TB0T";
-    let value = smc.read_key(key).map_err(err_str)?;
-    println!("{}", value);
-
-    // Get key information
-    let key_info = smc.get_key_info(key).map_err(err_str)?;
-    println!(
-        "data type: {}, size: {}",
-        String::from_utf8_lossy(&key_info.data_type.to_be_bytes()).trim(),
-        key_info.data_size
+fn generic_transport() {
+    let smc = IOService::with_transport(crate::mock::MockSmc::sample());
+    assert_eq!(smc.keys_count(), Ok(8));
+    let val = smc.read_key(b"TB0T").expect("TB0T exists");
+    assert_eq!(val.data_type, DataType::Flt);
+    assert!(!val.attributes.is_writable());
+    assert!(
+        smc.read_key(b"ACLC")
+            .is_ok_and(|v| v.attributes.is_readable() && v.attributes.is_writable())
     );
+    assert_eq!(val.valid_bytes(), 25.8f32.to_le_bytes());
+    assert_eq!(
+        smc.read_key(b"XXXX").map(|_| ()),
+        Err(SmcError::KeyNotFound)
+    );
+    let mismatch = SmcError::SizeMismatch {
+        expected: 1,
+        actual: 2,
+    };
+    assert_eq!(smc.write_key(b"ACLC", &[0; 2]), Err(mismatch));
+    assert_eq!(
+        smc.write_key(b"ACLC", &[0; 33]),
+        Err(SmcError::ValueTooLong(33))
+    );
+    assert_eq!(
+        smc.write_key(b"ACLC", &[3]),
+        Err(SmcError::PrivilegeViolation)
+    );
+    smc.transport().set_privileged(true);
+    assert_eq!(smc.write_key(b"ACLC", &[3]), Ok(()));
+    assert_eq!(smc.read_key(b"ACLC").map(|v| v.bytes[0]), Ok(3));
+    smc.transport().set_privileged(false);
+    let keys: Vec<_> = smc
+        .values_iter()
+        .expect("#KEY is readable")
+        .map(|v| v.map(|v| v.key).map_err(|e| e.error))
+        .collect();
+    assert_eq!(keys[0], Ok(SmcKey::new(b"#KEY")));
+    assert_eq!(keys[3], Err(SmcError::PrivilegeViolation));
+    assert_eq!(smc.list_all_values().map(|v| v.len()), Ok(7));
+}
+
+#[test]
+fn firmware_result_is_checked() {
+    let smc = IOService::with_transport(crate::mock::MockSmc::sample());
+    smc.transport().set_privileged(true);
+    let not_writable = SmcError::Firmware(FirmwareError::KeyNotWritable);
+    assert_eq!(smc.write_key(b"FNum", &[2]), Err(not_writable));
+    assert_eq!(smc.read_key(b"FNum").map(|v| v.bytes[0]), Ok(1));
+    smc.transport().insert(
+        *b"MSDW",
+        crate::mock::MockKey::new(b"ui8 ", crate::structs::SMC_ATTR_WRITE, &[0]),
+    );
+    let not_readable = SmcError::Firmware(FirmwareError::KeyNotReadable);
+    assert_eq!(smc.read_key(b"MSDW").map(|_| ()), Err(not_readable));
+    assert_eq!(SmcError::from_result(132), Some(SmcError::KeyNotFound));
+    for code in 0..=u8::MAX {
+        assert_eq!(FirmwareError::from_code(code).code(), code);
+    }
+}
 
-    let val_iter = smc.values_iter().map_err(err_str)?;
-    for _v in val_iter {}
-    let _values = smc.list_all_values().map_err(err_str)?;
-    let count = smc.keys_count().map_err(err_str)?;
-    println!("keys count: {}", count);
-    let err = smc.write_key(b"ACLC", &[0x03]).unwrap_err();
-    const PRIVILEGE_ERROR: i32 = -0x1FFFFD3F;
-    assert_eq!(err, PRIVILEGE_ERROR);
-    Ok(())
+#[test]
+fn write_typed_value() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::{SMC_ATTR_READ, SMC_ATTR_WRITE};
+
+    let mock = MockSmc::sample();
+    let rw = SMC_ATTR_READ | SMC_ATTR_WRITE;
+    mock.insert(*b"B0CT", MockKey::new(b"ui16", rw, &[0; 2]));
+    mock.insert(*b"RPlt", MockKey::new(b"ch8*", rw, &[0; 8]));
+    mock.set_privileged(true);
+    let smc = IOService::with_transport(mock);
+    assert_eq!(smc.write_value(b"ACLC", 3u8), Ok(()));
+    assert_eq!(
+        smc.read_key(b"ACLC").map(|v| v.data_value()),
+        Ok(Some(SmcValue::U8(3)))
+    );
+    assert_eq!(smc.write_value(b"B0CT", 300u32), Ok(()));
+    assert_eq!(
+        smc.read_key(b"B0CT").map(|v| v.data_value()),
+        Ok(Some(SmcValue::U16(300)))
+    );
+    assert_eq!(
+        smc.write_value(b"ACLC", 256u16),
+        Err(SmcError::Encode(EncodeError::OutOfRange(DataType::Ui8)))
+    );
+    assert_eq!(
+        smc.write_value(b"ACLC", true),
+        Err(SmcError::Encode(EncodeError::TypeMismatch(DataType::Ui8)))
+    );
+    assert_eq!(smc.write_value(b"RPlt", "j316"), Ok(()));
+    assert_eq!(
+        smc.read_key(b"RPlt").map(|v| v.valid_bytes().to_vec()),
+        Ok(b"j316\0\0\0\0".to_vec())
+    );
+}
+
+#[test]
+fn byte_order() {
+    use crate::mock::MockSmc;
+
+    let smc = IOService::with_transport(MockSmc::sample());
+    assert_eq!(smc.byte_order(), ByteOrder::Little);
+    let smc = smc
+        .with_byte_order(ByteOrder::Big)
+        .with_key_byte_order(b"TB0T", ByteOrder::Little);
+    assert_eq!(smc.byte_order_of(b"B0CT"), ByteOrder::Big);
+    let val = smc.read_key(b"B0CT").expect("B0CT exists");
+    assert_eq!(val.byte_order, ByteOrder::Big);
+    assert_eq!(val.data_value(), Some(SmcValue::U16(0x7b00)));
+    let val = smc.read_key(b"TB0T").expect("TB0T exists");
+    assert_eq!(val.data_value(), Some(SmcValue::F32(25.8)));
+    let orders: Vec<_> = smc
+        .values_iter()
+        .expect("#KEY is readable")
+        .filter_map(Result::ok)
+        .map(|v| (v.key, v.byte_order))
+        .collect();
+    assert!(orders.contains(&(SmcKey::new(b"TB0T"), ByteOrder::Little)));
+    assert!(orders.contains(&(SmcKey::new(b"F0Ac"), ByteOrder::Big)));
+
+    smc.transport().set_privileged(true);
+    assert_eq!(smc.write_value(b"ACLC", 3u8), Ok(()));
+    smc.transport().insert(
+        *b"B0CT",
+        crate::mock::MockKey::new(
+            b"ui16",
+            crate::structs::SMC_ATTR_WRITE | crate::structs::SMC_ATTR_READ,
+            &[0; 2],
+        ),
+    );
+    assert_eq!(smc.write_value(b"B0CT", 0x0102u16), Ok(()));
+    assert_eq!(
+        smc.read_key(b"B0CT").map(|v| v.valid_bytes().to_vec()),
+        Ok(vec![1, 2])
+    );
+}
+
+#[test]
+fn key_info_cache() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct Counting {
+        smc: crate::mock::MockSmc,
+        calls: AtomicU32,
+    }
+
+    impl SmcTransport for Counting {
+        fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.smc.call(input, output)
+        }
+    }
+
+    let smc = IOService::with_transport(Counting {
+        smc: crate::mock::MockSmc::sample(),
+        calls: AtomicU32::new(0),
+    });
+    let calls = || smc.transport().calls.swap(0, Ordering::Relaxed);
+    smc.read_key(b"TB0T").expect("TB0T exists");
+    smc.read_key(b"TB0T").expect("TB0T exists");
+    assert_eq!(calls(), 4);
+
+    let smc = smc.with_key_info_cache();
+    let calls = || smc.transport().calls.swap(0, Ordering::Relaxed);
+    smc.read_key(b"TB0T").expect("TB0T exists");
+    smc.read_key(b"TB0T").expect("TB0T exists");
+    assert_eq!(calls(), 3);
+    smc.transport().smc.set_privileged(true);
+    assert_eq!(smc.write_key(b"ACLC", &[3]), Ok(()));
+    assert_eq!(smc.write_key(b"ACLC", &[2]), Ok(()));
+    assert_eq!(calls(), 3);
+
+    // a stale size is dropped from the cache
+    smc.transport().smc.insert(
+        *b"TB0T",
+        crate::mock::MockKey::new(b"ui8 ", crate::structs::SMC_ATTR_READ, &[1]),
+    );
+    let mismatch = SmcError::Firmware(FirmwareError::KeySizeMismatch);
+    assert_eq!(smc.read_key(b"TB0T").map(|_| ()), Err(mismatch));
+    assert_eq!(smc.read_key(b"TB0T").map(|v| v.data_size), Ok(1));
+
+    smc.clear_key_info_cache();
+    assert_eq!(smc.cache_all_key_info(), Ok(()));
+    calls();
+    assert_eq!(smc.values_iter().map(|i| i.count()), Ok(8));
+    // #KEY, then one index lookup and one read for each key
+    assert_eq!(calls(), 1 + 8 * 2);
+    smc.invalidate_key_info(b"TB0T");
+    smc.read_key(b"TB0T").expect("TB0T exists");
+    assert_eq!(calls(), 2);
+}
+
+#[test]
+fn keys_iter() {
+    let smc = IOService::with_transport(crate::mock::MockSmc::sample());
+    let keys: Vec<_> = smc
+        .keys_iter()
+        .expect("#KEY is readable")
+        .map(|e| e.map(|e| (e.index, e.key, e.info.map(|i| i.data_size))))
+        .map(|e| e.map_err(|e| (e.key, e.error)))
+        .collect();
+    assert_eq!(keys.len(), 8);
+    assert_eq!(keys[1], Ok((1, SmcKey::new(b"ACLC"), Some(1))));
+    // the key info of private keys is readable
+    assert_eq!(keys[3], Ok((3, SmcKey::new(b"CHLS"), Some(1))));
+    let mut iter = smc.keys_iter().expect("#KEY is readable").without_info();
+    assert_eq!(iter.len(), 8);
+    let entry = iter.nth(3).and_then(Result::ok);
+    assert!(entry.is_some_and(|e| e.key == SmcKey::new(b"CHLS") && e.info.is_none()));
+    assert_eq!(iter.len(), 4);
+    assert!(iter.nth(10).is_none());
+    assert_eq!(iter.len(), 0);
+
+    let mut values = smc.values_iter().expect("#KEY is readable").skip(7);
+    assert_eq!(values.len(), 1);
+    assert!(
+        values
+            .next()
+            .is_some_and(|v| v.is_ok_and(|v| v.key == SmcKey::new(b"TB0T")))
+    );
+}
+
+#[test]
+fn index_lookup() {
+    let smc = IOService::with_transport(crate::mock::MockSmc::sample());
+    for (index, key) in [(0, b"#KEY"), (1, b"ACLC"), (4, b"F0Ac"), (7, b"TB0T")] {
+        assert_eq!(smc.key_at_index(index), Ok(SmcKey::new(key)));
+        assert_eq!(smc.index_of(key), Ok(index));
+    }
+    assert_eq!(smc.index_of(b"AAAA"), Err(SmcError::KeyNotFound));
+    assert_eq!(smc.index_of(b"F0Ab"), Err(SmcError::KeyNotFound));
+    assert_eq!(smc.index_of(b"ZZZZ"), Err(SmcError::KeyNotFound));
+    assert!(smc.key_at_index(8).is_err());
+
+    let between = |first: &[u8; 4], last: &[u8; 4]| -> Vec<SmcKey> {
+        smc.keys_between(first, last)
+            .expect("#KEY is readable")
+            .without_info()
+            .filter_map(Result::ok)
+            .map(|e| e.key)
+            .collect()
+    };
+    assert_eq!(
+        between(b"B000", b"FZZZ"),
+        [b"B0CT", b"CHLS", b"F0Ac", b"FNum"].map(SmcKey::from)
+    );
+    assert_eq!(between(b"ACLC", b"ACLC"), [SmcKey::new(b"ACLC")]);
+    assert_eq!(between(b"T000", b"TZZZ"), [SmcKey::new(b"TB0T")]);
+    assert_eq!(between(b"U000", b"ZZZZ"), []);
+    assert_eq!(between(b"Z000", b"A000"), []);
+    let iter = smc
+        .keys_between(b"B000", b"FZZZ")
+        .expect("#KEY is readable");
+    assert_eq!(iter.len(), 4);
+}
+
+#[test]
+fn read_many() {
+    use crate::mock::MockSmc;
+
+    let smc = IOService::with_transport(MockSmc::sample()).with_key_info_cache();
+    let keys = [b"TB0T", b"CHLS", b"XXXX", b"ACLC"].map(SmcKey::from);
+    let values: Vec<_> = smc
+        .read_many(&keys)
+        .into_iter()
+        .map(|v| v.map(|v| (v.key, v.data_size)))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Ok((keys[0], 4)),
+            Err(SmcError::PrivilegeViolation),
+            Err(SmcError::KeyNotFound),
+            Ok((keys[3], 1)),
+        ]
+    );
+    assert!(smc.read_many(&[]).is_empty());
 }
@@ -0,0 +1,179 @@
+//! IOKit transport, used by [`IOService`] on macOS.
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::structs::{KERNEL_INDEX_SMC, SMCKeyData};
+use crate::value::ByteOrder;
+use libc::{ENOENT, KERN_SUCCESS, mach_port_t, sysctlbyname};
+use objc2_io_kit::{
+    IOConnectCallStructMethod, IOIteratorNext, IOMainPort, IOObjectRelease, IOServiceClose,
+    IOServiceGetMatchingServices, IOServiceMatching, IOServiceOpen, io_connect_t,
+};
+use std::ffi::c_void;
+
+unsafe extern "C" {
+    pub fn mach_task_self() -> mach_port_t;
+}
+
+/// Connection to the AppleSMC service.
+///
+/// It is opened by [`IOService::init`] and closed when dropped. The byte
+/// order of the SMC is detected when it is opened, see
+/// [`SmcTransport::byte_order`].
+pub struct IOKitConnection {
+    conn: io_connect_t,
+    byte_order: ByteOrder,
+}
+
+impl SmcTransport for IOKitConnection {
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        unsafe {
+            let mut output_struct_cnt = size_of::<SMCKeyData>();
+            let res = IOConnectCallStructMethod(
+                self.conn,
+                KERNEL_INDEX_SMC,
+                input as *const _ as *const c_void,
+                size_of::<SMCKeyData>(),
+                output as *mut _ as *mut c_void,
+                &raw mut output_struct_cnt,
+            );
+            if res == KERN_SUCCESS {
+                Ok(())
+            } else {
+                Err(SmcError::from_kern(res))
+            }
+        }
+    }
+
+    /// Returns the byte order of the SMC of the Mac the program runs on.
+    ///
+    /// The SMC of Apple Silicon is little endian, also for `x86_64` programs
+    /// translated by Rosetta, so it is detected from the `hw.optional.arm64`
+    /// sysctl, not from the target of the build.
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+}
+
+/// Detects the byte order of the SMC, see [`IOKitConnection::byte_order`].
+///
+/// Intel Macs without the `hw.optional.arm64` sysctl are big endian, other
+/// failures fall back to [`ByteOrder::native`].
+fn detect_byte_order() -> ByteOrder {
+    let mut arm64: i32 = 0;
+    let mut len = size_of::<i32>();
+    let res = unsafe {
+        sysctlbyname(
+            c"hw.optional.arm64".as_ptr(),
+            (&raw mut arm64).cast(),
+            &raw mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if res == 0 {
+        if arm64 != 0 {
+            ByteOrder::Little
+        } else {
+            ByteOrder::Big
+        }
+    } else if std::io::Error::last_os_error().raw_os_error() == Some(ENOENT) {
+        ByteOrder::Big
+    } else {
+        ByteOrder::native()
+    }
+}
+
+impl Drop for IOKitConnection {
+    fn drop(&mut self) {
+        IOServiceClose(self.conn);
+    }
+}
+
+impl IOService<IOKitConnection> {
+    /// Initializes a connection to the Apple SMC.
+    ///
+    /// This function opens the IOKit connection to the AppleSMC service
+    /// and returns a handle that can be used to interact with SMC keys.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(IOService)` - A handle to the SMC service
+    /// - `Err(SmcError)` - The error if initialization fails
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The IO main port cannot be initialized, or the AppleSMC service
+    ///   cannot be looked up ([`SmcError::Transport`])
+    /// - The AppleSMC service cannot be found ([`SmcError::ServiceNotFound`])
+    /// - The IOService cannot be opened ([`SmcError::OpenFailed`])
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use smc_lib::io::IOService;
+    ///
+    /// let smc = IOService::init().unwrap();
+    /// println!("Successfully connected to SMC");
+    /// ```
+    pub fn init() -> Result<Self, SmcError> {
+        unsafe {
+            let mut main_port = 0;
+            let res = IOMainPort(0, &raw mut main_port);
+            if res != KERN_SUCCESS {
+                return Err(SmcError::from_kern(res));
+            }
+            let matching_dict =
+                IOServiceMatching(c"AppleSMC".as_ptr()).and_then(|d| d.downcast().ok());
+            let mut iterator = 0;
+            let res = IOServiceGetMatchingServices(main_port, matching_dict, &raw mut iterator);
+            if res != KERN_SUCCESS {
+                return Err(SmcError::from_kern(res));
+            }
+            let device = IOIteratorNext(iterator);
+            IOObjectRelease(iterator);
+            if device == 0 {
+                return Err(SmcError::ServiceNotFound);
+            }
+            let mut conn = 0;
+            let res = IOServiceOpen(device, mach_task_self(), 0, &raw mut conn);
+            IOObjectRelease(device);
+            if res != KERN_SUCCESS {
+                return Err(SmcError::OpenFailed(res));
+            }
+            Ok(Self::with_transport(IOKitConnection {
+                conn,
+                byte_order: detect_byte_order(),
+            }))
+        }
+    }
+}
+
+#[test]
+#[ignore = "the key may not exist"]
+fn basic_example() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize SMC connection
+    let smc = IOService::init()?;
+
+    // Read a key (e.g. battery temperature)
+    let key = b"TB0T";
+    let value = smc.read_key(key)?;
+    println!("{}", value);
+
+    // Get key information
+    let key_info = smc.get_key_info(key)?;
+    println!(
+        "data type: {}, size: {}",
+        key_info.data_type().to_string().trim(),
+        key_info.data_size
+    );
+
+    let val_iter = smc.values_iter()?;
+    for _v in val_iter {}
+    let _values = smc.list_all_values()?;
+    let count = smc.keys_count()?;
+    println!("keys count: {}", count);
+    let err = smc.write_key(b"ACLC", &[0x03]).unwrap_err();
+    assert_eq!(err, SmcError::PrivilegeViolation);
+    Ok(())
+}
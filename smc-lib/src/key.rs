@@ -0,0 +1,364 @@
+//! SMC key names and attributes.
+
+use crate::structs::{
+    SMC_ATTR_ATOMIC, SMC_ATTR_CONST, SMC_ATTR_FUNCTION, SMC_ATTR_PRIVATE_READ,
+    SMC_ATTR_PRIVATE_WRITE, SMC_ATTR_READ, SMC_ATTR_WRITE,
+};
+use std::str::FromStr;
+
+/// Name of a SMC key, four ASCII characters such as `TB0T`.
+///
+/// The SMC stores keys as big endian `u32`, see [`From<u32>`](#impl-From<u32>-for-SmcKey)
+/// and [`to_u32`](Self::to_u32). Keys are ordered like the SMC key table.
+///
+/// [`new`](Self::new) and [`FromStr`] check that the key is ASCII. The `From`
+/// conversions from `[u8; 4]` and `u32` do not, they take the keys returned
+/// by the SMC as they are, so a key may hold any four bytes. `Display` writes
+/// the bytes that are not ASCII as `\xNN` escapes, such keys do not parse
+/// back.
+///
+/// # Example
+///
+/// ```
+/// use smc_lib::key::SmcKey;
+///
+/// // checked at compile time
+/// const BATTERY_TEMP: SmcKey = SmcKey::new(b"TB0T");
+///
+/// let key: SmcKey = "TB0T".parse().unwrap();
+/// assert_eq!(key, BATTERY_TEMP);
+/// assert_eq!(key.to_string(), "TB0T");
+/// assert_eq!(key.to_u32(), 0x5442_3054);
+/// assert!("TB0".parse::<SmcKey>().is_err());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SmcKey([u8; 4]);
+
+/// Number of keys in a numbered family such as `F{n}Ac` or `TB{n}T`.
+///
+/// The index is a single digit of the key name, so it goes from `0` to `9`.
+/// Fans and sensors with a higher index can not be named and are not read.
+pub const INDEXED_KEY_LIMIT: u8 = 10;
+
+impl SmcKey {
+    /// Creates a key from its four characters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not ASCII, at compile time in a `const` context.
+    pub const fn new(key: &[u8; 4]) -> Self {
+        assert!(key.is_ascii(), "SMC key must be ASCII");
+        Self(*key)
+    }
+
+    /// Creates a key from the big endian `u32` used by the SMC.
+    pub const fn from_u32(key: u32) -> Self {
+        Self(key.to_be_bytes())
+    }
+
+    /// Returns the big endian `u32` used by the SMC.
+    pub const fn to_u32(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    /// Returns the four bytes of the key.
+    pub const fn as_bytes(&self) -> &[u8; 4] {
+        &self.0
+    }
+}
+
+impl From<[u8; 4]> for SmcKey {
+    fn from(key: [u8; 4]) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&[u8; 4]> for SmcKey {
+    fn from(key: &[u8; 4]) -> Self {
+        Self(*key)
+    }
+}
+
+impl From<u32> for SmcKey {
+    fn from(key: u32) -> Self {
+        Self::from_u32(key)
+    }
+}
+
+impl From<SmcKey> for u32 {
+    fn from(key: SmcKey) -> Self {
+        key.to_u32()
+    }
+}
+
+impl From<SmcKey> for [u8; 4] {
+    fn from(key: SmcKey) -> Self {
+        key.0
+    }
+}
+
+impl std::fmt::Display for SmcKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for &b in &self.0 {
+            if b.is_ascii() {
+                write!(f, "{}", char::from(b))?;
+            } else {
+                write!(f, "\\x{:02x}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SmcKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SmcKey({:?})", self.to_string())
+    }
+}
+
+/// Error returned when a string can not be parsed into a [`SmcKey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyParseError {
+    /// The key is not 4 characters long, contains the length in bytes
+    InvalidLength(usize),
+    /// The key contains non-ASCII characters
+    NonAscii,
+}
+
+impl std::fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyParseError::InvalidLength(len) => {
+                write!(f, "SMC key must be 4 characters, got {} bytes", len)
+            }
+            KeyParseError::NonAscii => write!(f, "SMC key must be ASCII"),
+        }
+    }
+}
+
+impl std::error::Error for KeyParseError {}
+
+impl FromStr for SmcKey {
+    type Err = KeyParseError;
+
+    /// Parses a key, keys shorter than 4 characters need their trailing
+    /// spaces, e.g. `"FS! "`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(KeyParseError::NonAscii);
+        }
+        let key = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| KeyParseError::InvalidLength(s.len()))?;
+        Ok(Self(key))
+    }
+}
+
+/// Attributes of a SMC key, the `data_attributes` bits of the key info.
+///
+/// Displays like `ls -l` permissions, one letter per known bit or `-`:
+///
+/// | Letter | Bit                              | Meaning                          |
+/// |--------|----------------------------------|----------------------------------|
+/// | `r`    | [`KeyAttributes::READ`]          | the key can be read              |
+/// | `w`    | [`KeyAttributes::WRITE`]         | the key can be written           |
+/// | `f`    | [`KeyAttributes::FUNCTION`]      | the key is a function/command    |
+/// | `c`    | [`KeyAttributes::CONST`]         | the value never changes          |
+/// | `a`    | [`KeyAttributes::ATOMIC`]        | the value is accessed atomically |
+/// | `R`    | [`KeyAttributes::PRIVATE_READ`]  | reading needs privilege          |
+/// | `W`    | [`KeyAttributes::PRIVATE_WRITE`] | writing needs privilege          |
+///
+/// # Example
+///
+/// ```
+/// use smc_lib::key::KeyAttributes;
+///
+/// let attrs = KeyAttributes::from_bits(0xc0);
+/// assert!(attrs.is_readable() && attrs.is_writable());
+/// assert!(!attrs.is_private_write());
+/// assert_eq!(attrs.to_string(), "rw-----");
+/// assert_eq!(attrs, KeyAttributes::READ | KeyAttributes::WRITE);
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct KeyAttributes(u8);
+
+impl KeyAttributes {
+    pub const READ: Self = Self(SMC_ATTR_READ);
+    pub const WRITE: Self = Self(SMC_ATTR_WRITE);
+    pub const FUNCTION: Self = Self(SMC_ATTR_FUNCTION);
+    pub const CONST: Self = Self(SMC_ATTR_CONST);
+    pub const ATOMIC: Self = Self(SMC_ATTR_ATOMIC);
+    pub const PRIVATE_READ: Self = Self(SMC_ATTR_PRIVATE_READ);
+    pub const PRIVATE_WRITE: Self = Self(SMC_ATTR_PRIVATE_WRITE);
+
+    /// Known bits with their letter, in display order.
+    const LETTERS: [(Self, char); 7] = [
+        (Self::READ, 'r'),
+        (Self::WRITE, 'w'),
+        (Self::FUNCTION, 'f'),
+        (Self::CONST, 'c'),
+        (Self::ATOMIC, 'a'),
+        (Self::PRIVATE_READ, 'R'),
+        (Self::PRIVATE_WRITE, 'W'),
+    ];
+
+    /// Creates the attributes from the raw bits, unknown bits are kept.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw bits.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if all bits of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_readable(self) -> bool {
+        self.contains(Self::READ)
+    }
+
+    pub const fn is_writable(self) -> bool {
+        self.contains(Self::WRITE)
+    }
+
+    pub const fn is_function(self) -> bool {
+        self.contains(Self::FUNCTION)
+    }
+
+    pub const fn is_const(self) -> bool {
+        self.contains(Self::CONST)
+    }
+
+    pub const fn is_atomic(self) -> bool {
+        self.contains(Self::ATOMIC)
+    }
+
+    pub const fn is_private_read(self) -> bool {
+        self.contains(Self::PRIVATE_READ)
+    }
+
+    pub const fn is_private_write(self) -> bool {
+        self.contains(Self::PRIVATE_WRITE)
+    }
+
+    /// Parses the `Display` form, e.g. `rw-----`.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let mut attrs = Self::default();
+        for (bit, letter) in Self::LETTERS {
+            match chars.next()? {
+                '-' => {}
+                c if c == letter => attrs = attrs | bit,
+                _ => return None,
+            }
+        }
+        chars.next().is_none().then_some(attrs)
+    }
+}
+
+impl std::ops::BitOr for KeyAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl From<u8> for KeyAttributes {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<KeyAttributes> for u8 {
+    fn from(attrs: KeyAttributes) -> Self {
+        attrs.0
+    }
+}
+
+impl std::fmt::Display for KeyAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (bit, letter) in Self::LETTERS {
+            let c = if self.contains(bit) { letter } else { '-' };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for KeyAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "KeyAttributes({:#04x} {})", self.0, self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SmcKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SmcKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn parse_key() {
+    assert_eq!("FS! ".parse(), Ok(SmcKey::new(b"FS! ")));
+    assert_eq!(
+        "FS!".parse::<SmcKey>(),
+        Err(KeyParseError::InvalidLength(3))
+    );
+    assert_eq!(
+        "TB0T0".parse::<SmcKey>(),
+        Err(KeyParseError::InvalidLength(5))
+    );
+    assert_eq!("TB0é".parse::<SmcKey>(), Err(KeyParseError::NonAscii));
+    assert_eq!(SmcKey::from(0x234b_4559), SmcKey::new(b"#KEY"));
+    let raw = SmcKey::from([b'T', 0xc3, 0xa9, b'T']);
+    assert_eq!(raw.to_string(), "T\\xc3\\xa9T");
+    assert!(raw.to_string().parse::<SmcKey>().is_err());
+    assert!(SmcKey::new(b"#KEY") < SmcKey::new(b"TB0T"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_key() {
+    use serde::{Deserialize, de::value::StrDeserializer};
+
+    let de = StrDeserializer::<serde::de::value::Error>::new("TB0T");
+    assert_eq!(SmcKey::deserialize(de), Ok(SmcKey::new(b"TB0T")));
+    let de = StrDeserializer::<serde::de::value::Error>::new("TB0");
+    assert!(SmcKey::deserialize(de).is_err());
+}
+
+#[test]
+fn key_attributes() {
+    let attrs = KeyAttributes::from_bits(0xff);
+    assert_eq!(attrs.to_string(), "rwfcaRW");
+    assert_eq!(
+        KeyAttributes::parse("rwfcaRW").map(|a| a.bits()),
+        Some(0xdf)
+    );
+    assert_eq!(
+        KeyAttributes::parse("-------"),
+        Some(KeyAttributes::default())
+    );
+    assert_eq!(
+        KeyAttributes::parse("r-----W"),
+        Some(KeyAttributes::READ | KeyAttributes::PRIVATE_WRITE)
+    );
+    assert_eq!(KeyAttributes::parse("w------"), None);
+    assert_eq!(KeyAttributes::parse("rw----"), None);
+    assert_eq!(KeyAttributes::parse("rw------"), None);
+}
@@ -4,10 +4,28 @@
 //! SMC keys, which control various hardware parameters such as temperatures,
 //! fan speeds, battery status, and more.
 //!
+//! All SMC operations are implemented on top of the [`io::SmcTransport`]
+//! trait, so the library also builds on other platforms, where a custom
+//! transport can be plugged into [`io::IOService`].
+//!
 
-#![cfg(target_os = "macos")]
 #![deny(clippy::unwrap_used)]
 
+pub mod battery;
+pub mod catalog;
+pub mod charge;
+pub mod data_type;
+pub mod fan;
 pub mod io;
+#[cfg(target_os = "macos")]
+pub mod iokit;
+pub mod key;
+pub mod mock;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod shared;
+pub mod snapshot;
 pub mod structs;
+pub mod sysfs;
 pub mod value;
+pub mod watch;
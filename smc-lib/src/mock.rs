@@ -0,0 +1,307 @@
+//! In-memory simulated SMC.
+//!
+//! [`MockSmc`] is a [`SmcTransport`] holding a table of keys. It answers the
+//! SMC commands the same way as the firmware does, so code written against
+//! [`IOService`](crate::io::IOService) can be tested without a Mac.
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::io::IOService;
+//! use smc_lib::mock::{MockKey, MockSmc};
+//! use smc_lib::structs::{SMC_ATTR_READ, SMC_ATTR_WRITE};
+//!
+//! let mock = MockSmc::new();
+//! mock.insert(*b"ACLC", MockKey::new(b"ui8 ", SMC_ATTR_READ | SMC_ATTR_WRITE, &[0x01]));
+//! mock.set_privileged(true);
+//!
+//! let smc = IOService::with_transport(mock);
+//! smc.write_key(b"ACLC", &[0x03]).unwrap();
+//! assert_eq!(smc.read_key(b"ACLC").unwrap().valid_bytes(), [0x03]);
+//! ```
+
+use crate::data_type::DataType;
+use crate::io::{SmcError, SmcTransport};
+use crate::key::SmcKey;
+use crate::structs::{
+    SMC_ATTR_PRIVATE_READ, SMC_ATTR_PRIVATE_WRITE, SMC_ATTR_READ, SMC_ATTR_WRITE, SMC_BYTES_LEN,
+    SMC_CMD_READ_BYTES, SMC_CMD_READ_INDEX, SMC_CMD_READ_KEYINFO, SMC_CMD_WRITE_BYTES,
+    SMC_RESULT_BAD_COMMAND, SMC_RESULT_KEY_INDEX_RANGE_ERROR, SMC_RESULT_KEY_NOT_FOUND,
+    SMC_RESULT_KEY_SIZE_MISMATCH, SMC_RESULT_NOT_READABLE, SMC_RESULT_NOT_WRITABLE, SMCBytes,
+    SMCKeyData, SMCKeyData_keyInfo,
+};
+use crate::value::ByteOrder;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// A key stored in [`MockSmc`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MockKey {
+    pub data_type: DataType,
+    pub data_size: u32,
+    /// See the `SMC_ATTR_*` constants in [`crate::structs`]
+    pub data_attributes: u8,
+    pub bytes: SMCBytes,
+}
+
+impl MockKey {
+    /// Creates a key holding `bytes`, its size is the length of `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is longer than [`SMC_BYTES_LEN`].
+    pub fn new(data_type: impl Into<DataType>, data_attributes: u8, bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= SMC_BYTES_LEN, "SMC value is too long");
+        let mut key = Self {
+            data_type: data_type.into(),
+            data_size: bytes.len() as u32,
+            data_attributes,
+            ..Default::default()
+        };
+        key.bytes[..bytes.len()].copy_from_slice(bytes);
+        key
+    }
+
+    fn key_info(&self) -> SMCKeyData_keyInfo {
+        SMCKeyData_keyInfo {
+            data_size: self.data_size,
+            data_type: u32::from(self.data_type),
+            data_attributes: self.data_attributes,
+        }
+    }
+}
+
+/// A simulated SMC, answering commands from an in-memory key table.
+///
+/// Like the firmware, the table is sorted by key and always contains `#KEY`,
+/// which holds the number of keys. The mock reproduces the firmware results:
+///
+/// - missing keys are reported with `result == 132` (`SMC_RESULT_KEY_NOT_FOUND`)
+/// - keys without the read or write attribute can not be read or written
+/// - the size of the request must match the size of the key
+/// - writes, and reads and writes of private keys, fail with a privilege
+///   violation unless [`set_privileged`](Self::set_privileged) is called,
+///   like an IOKit connection opened by a non-root user. The key info of
+///   private keys is readable by anyone.
+///
+/// All methods take `&self`, so the table can be changed while the mock is
+/// used by an [`IOService`](crate::io::IOService), through
+/// [`IOService::transport`](crate::io::IOService::transport).
+#[derive(Debug)]
+pub struct MockSmc {
+    keys: Mutex<BTreeMap<SmcKey, MockKey>>,
+    privileged: AtomicBool,
+}
+
+impl Default for MockSmc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockSmc {
+    /// Creates a SMC whose table only contains `#KEY`.
+    pub fn new() -> Self {
+        let mock = Self {
+            keys: Mutex::new(BTreeMap::new()),
+            privileged: AtomicBool::new(false),
+        };
+        mock.update_count(&mut mock.lock());
+        mock
+    }
+
+    /// Creates a SMC with a small key table taken from an Apple Silicon Mac.
+    ///
+    /// | key    | type   | value                                  |
+    /// |--------|--------|----------------------------------------|
+    /// | `ACLC` | `ui8 ` | MagSafe light, writable                |
+    /// | `B0CT` | `ui16` | battery cycle count, 123               |
+    /// | `CHLS` | `ui8 ` | private, reading needs privilege       |
+    /// | `F0Ac` | `flt ` | fan speed, 1200.0                      |
+    /// | `FNum` | `ui8 ` | number of fans, 1                      |
+    /// | `RPlt` | `ch8*` | platform, `"j314s"`                    |
+    /// | `TB0T` | `flt ` | battery temperature, 25.8              |
+    pub fn sample() -> Self {
+        let mock = Self::new();
+        let r = SMC_ATTR_READ;
+        let rw = SMC_ATTR_READ | SMC_ATTR_WRITE;
+        let keys = [
+            (*b"ACLC", MockKey::new(b"ui8 ", rw, &[0x01])),
+            (*b"B0CT", MockKey::new(b"ui16", r, &123u16.to_le_bytes())),
+            (
+                *b"CHLS",
+                MockKey::new(
+                    b"ui8 ",
+                    rw | SMC_ATTR_PRIVATE_READ | SMC_ATTR_PRIVATE_WRITE,
+                    &[0],
+                ),
+            ),
+            (*b"F0Ac", MockKey::new(b"flt ", r, &1200f32.to_le_bytes())),
+            (*b"FNum", MockKey::new(b"ui8 ", r, &[1])),
+            (*b"RPlt", MockKey::new(b"ch8*", r, b"j314s\0\0\0")),
+            (*b"TB0T", MockKey::new(b"flt ", r, &25.8f32.to_le_bytes())),
+        ];
+        for (key, value) in keys {
+            mock.insert(key, value);
+        }
+        mock
+    }
+
+    /// Inserts or replaces a key, returning the previous one.
+    ///
+    /// `#KEY` is maintained by the mock, inserting it has no effect.
+    pub fn insert(&self, key: impl Into<SmcKey>, value: MockKey) -> Option<MockKey> {
+        let mut keys = self.lock();
+        let old = keys.insert(key.into(), value);
+        self.update_count(&mut keys);
+        old
+    }
+
+    /// Removes a key, returning it.
+    pub fn remove(&self, key: impl Into<SmcKey>) -> Option<MockKey> {
+        let mut keys = self.lock();
+        let old = keys.remove(&key.into());
+        self.update_count(&mut keys);
+        old
+    }
+
+    /// Returns a copy of a key, e.g. to check what was written to it.
+    pub fn get(&self, key: impl Into<SmcKey>) -> Option<MockKey> {
+        self.lock().get(&key.into()).copied()
+    }
+
+    /// Sets whether the caller is privileged (root).
+    pub fn set_privileged(&self, privileged: bool) {
+        self.privileged.store(privileged, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<SmcKey, MockKey>> {
+        self.keys.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn update_count(&self, keys: &mut BTreeMap<SmcKey, MockKey>) {
+        keys.remove(&SmcKey::new(b"#KEY"));
+        let count = keys.len() as u32 + 1;
+        keys.insert(
+            SmcKey::new(b"#KEY"),
+            MockKey::new(b"ui32", SMC_ATTR_READ, &count.to_be_bytes()),
+        );
+    }
+}
+
+impl SmcTransport for MockSmc {
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        *output = SMCKeyData::default();
+        let privileged = self.privileged.load(Ordering::Relaxed);
+        let mut keys = self.lock();
+        if input.data8 == SMC_CMD_READ_INDEX {
+            match keys.keys().nth(input.data32 as usize) {
+                Some(key) => output.key = key.to_u32(),
+                None => output.result = SMC_RESULT_KEY_INDEX_RANGE_ERROR,
+            }
+            return Ok(());
+        }
+        if input.data8 == SMC_CMD_WRITE_BYTES && !privileged {
+            return Err(SmcError::PrivilegeViolation);
+        }
+        let Some(entry) = keys.get_mut(&SmcKey::from(input.key)) else {
+            output.result = match input.data8 {
+                SMC_CMD_READ_KEYINFO | SMC_CMD_READ_BYTES => SMC_RESULT_KEY_NOT_FOUND,
+                _ => SMC_RESULT_BAD_COMMAND,
+            };
+            return Ok(());
+        };
+        let private = match input.data8 {
+            SMC_CMD_READ_BYTES => SMC_ATTR_PRIVATE_READ,
+            SMC_CMD_WRITE_BYTES => SMC_ATTR_PRIVATE_WRITE,
+            _ => 0,
+        };
+        if entry.data_attributes & private != 0 && !privileged {
+            return Err(SmcError::PrivilegeViolation);
+        }
+        match input.data8 {
+            SMC_CMD_READ_KEYINFO => output.key_info = entry.key_info(),
+            SMC_CMD_READ_BYTES => {
+                if entry.data_attributes & SMC_ATTR_READ == 0 {
+                    output.result = SMC_RESULT_NOT_READABLE;
+                } else if input.key_info.data_size != entry.data_size {
+                    output.result = SMC_RESULT_KEY_SIZE_MISMATCH;
+                } else {
+                    output.bytes = entry.bytes;
+                }
+            }
+            SMC_CMD_WRITE_BYTES => {
+                if entry.data_attributes & SMC_ATTR_WRITE == 0 {
+                    output.result = SMC_RESULT_NOT_WRITABLE;
+                } else if input.key_info.data_size != entry.data_size {
+                    output.result = SMC_RESULT_KEY_SIZE_MISMATCH;
+                } else {
+                    let size = entry.data_size as usize;
+                    entry.bytes = SMCBytes::default();
+                    entry.bytes[..size].copy_from_slice(&input.bytes[..size]);
+                }
+            }
+            _ => output.result = SMC_RESULT_BAD_COMMAND,
+        }
+        Ok(())
+    }
+
+    /// The mock behaves like an Apple Silicon SMC.
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Little
+    }
+}
+
+#[test]
+fn firmware_results() {
+    let mock = MockSmc::sample();
+    let mut output = SMCKeyData::default();
+    let mut request = |data8, key: &[u8; 4], data32| {
+        let input = SMCKeyData {
+            key: u32::from_be_bytes(*key),
+            data8,
+            data32,
+            key_info: SMCKeyData_keyInfo {
+                data_size: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        mock.call(&input, &mut output).map(|_| output)
+    };
+    let count = request(SMC_CMD_READ_KEYINFO, b"#KEY", 0).map(|o| o.key_info.data_size);
+    assert_eq!(count, Ok(4));
+    let key = request(SMC_CMD_READ_INDEX, b"\0\0\0\0", 0).map(|o| o.key.to_be_bytes());
+    assert_eq!(key, Ok(*b"#KEY"));
+    let result = request(SMC_CMD_READ_INDEX, b"\0\0\0\0", 8).map(|o| o.result);
+    assert_eq!(result, Ok(SMC_RESULT_KEY_INDEX_RANGE_ERROR));
+    let result = request(SMC_CMD_READ_KEYINFO, b"XXXX", 0).map(|o| o.result);
+    assert_eq!(result, Ok(132));
+    let result = request(SMC_CMD_READ_BYTES, b"B0CT", 0).map(|o| o.result);
+    assert_eq!(result, Ok(SMC_RESULT_KEY_SIZE_MISMATCH));
+    let result = request(SMC_CMD_READ_KEYINFO, b"CHLS", 0).map(|o| o.result);
+    assert_eq!(result, Ok(0));
+    let result = request(SMC_CMD_READ_BYTES, b"CHLS", 0).map(|o| o.result);
+    assert_eq!(result, Err(SmcError::PrivilegeViolation));
+    let result = request(SMC_CMD_WRITE_BYTES, b"ACLC", 0).map(|o| o.result);
+    assert_eq!(result, Err(SmcError::PrivilegeViolation));
+    mock.set_privileged(true);
+    let result = request(SMC_CMD_READ_BYTES, b"CHLS", 0).map(|o| o.result);
+    assert_eq!(result, Ok(0));
+    let result = request(SMC_CMD_WRITE_BYTES, b"FNum", 0).map(|o| o.result);
+    assert_eq!(result, Ok(SMC_RESULT_NOT_WRITABLE));
+}
+
+#[test]
+fn key_count_follows_table() {
+    let mock = MockSmc::sample();
+    assert_eq!(mock.get(b"#KEY").map(|k| k.bytes[3]), Some(8));
+    mock.remove(b"CHLS");
+    mock.insert(*b"#KEY", MockKey::default());
+    assert_eq!(mock.get(b"#KEY").map(|k| k.bytes[3]), Some(7));
+}
@@ -0,0 +1,296 @@
+//! Async SMC operations, enabled by the `async` feature.
+//!
+//! SMC calls block the calling thread until the SMC answers, which stalls an
+//! async runtime. [`AsyncSmc`] owns an [`IOService`] on a dedicated worker
+//! thread, and every operation is sent to that thread, so the calls are also
+//! serialized. The futures and [`ValStream`] work with any executor, tokio is
+//! only used for its channels.
+//!
+//! # Cancellation
+//!
+//! Once a future returned by [`AsyncSmc`] has been polled, its SMC call is
+//! queued on the worker thread. Dropping the future does not cancel the call,
+//! only its result is discarded:
+//!
+//! - reads and [`AsyncSmc::get_key_info`] are cancellation safe, nothing is
+//!   lost but the value;
+//! - a dropped [`AsyncSmc::write_key`] may or may not have written the key.
+//!   Read the key again when it matters, e.g. after a `select!` timeout;
+//! - [`ValStream`] is cancellation safe, a value whose `next()` was dropped
+//!   is yielded by the next call.
+//!
+//! The worker thread stops, and the connection is closed, once every clone
+//! of the [`AsyncSmc`] and every [`ValStream`] are dropped and the queued
+//! calls are done.
+
+use crate::io::{IOService, SmcError, SmcTransport, ValError, ValIter};
+use crate::key::SmcKey;
+use crate::structs::{SMCKeyData_keyInfo, SMCVal};
+use futures_core::Stream;
+use std::{
+    future::Future,
+    panic::{AssertUnwindSafe, catch_unwind, resume_unwind},
+    pin::Pin,
+    sync::mpsc,
+    task::{Context, Poll, ready},
+    thread,
+};
+use tokio::sync::oneshot;
+
+type Job<T> = Box<dyn FnOnce(&IOService<T>) + Send>;
+
+/// Receives the result of a job, `Err` if the job panicked.
+type JobResult<R> = oneshot::Receiver<thread::Result<R>>;
+
+/// Async handle of the SMC, the SMC calls run on a worker thread.
+///
+/// `AsyncSmc` is cheap to clone, clones share the worker thread and the
+/// connection. See the [module documentation](self) for cancellation.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(target_os = "macos")] {
+/// use smc_lib::nonblocking::AsyncSmc;
+///
+/// # async fn example() -> Result<(), smc_lib::io::SmcError> {
+/// let smc = AsyncSmc::init()?;
+/// let value = smc.read_key(b"TB0T").await?;
+/// println!("{}", value);
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+#[cfg(target_os = "macos")]
+pub struct AsyncSmc<T = crate::iokit::IOKitConnection> {
+    jobs: mpsc::Sender<Job<T>>,
+}
+
+/// Async handle of the SMC, the SMC calls run on a worker thread.
+///
+/// `AsyncSmc` is cheap to clone, clones share the worker thread and the
+/// connection. See the [module documentation](self) for cancellation.
+#[cfg(not(target_os = "macos"))]
+pub struct AsyncSmc<T> {
+    jobs: mpsc::Sender<Job<T>>,
+}
+
+#[cfg(target_os = "macos")]
+impl AsyncSmc {
+    /// Opens the AppleSMC service, see [`IOService::init`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker thread can not be spawned.
+    pub fn init() -> Result<Self, SmcError> {
+        IOService::init().map(Self::new)
+    }
+}
+
+impl<T: SmcTransport + Send + 'static> AsyncSmc<T> {
+    /// Moves `service` to a new worker thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker thread can not be spawned.
+    pub fn new(service: IOService<T>) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job<T>>();
+        thread::Builder::new()
+            .name("smc-worker".into())
+            .spawn(move || {
+                for job in queue {
+                    job(&service);
+                }
+            })
+            .expect("failed to spawn the SMC worker thread");
+        Self { jobs }
+    }
+
+    /// Creates a handle that talks to a SMC through `transport`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker thread can not be spawned.
+    pub fn with_transport(transport: T) -> Self {
+        Self::new(IOService::with_transport(transport))
+    }
+
+    /// Runs `f` on the worker thread and returns its result.
+    ///
+    /// Use it for the operations of [`IOService`] without an async version,
+    /// the calls made by `f` are not interleaved with other calls. A panic
+    /// in `f` is resumed in the caller.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::mock::MockSmc;
+    /// use smc_lib::nonblocking::AsyncSmc;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let smc = AsyncSmc::with_transport(MockSmc::sample());
+    /// assert_eq!(smc.run(|smc| smc.keys_count()).await, Ok(8));
+    /// # });
+    /// ```
+    pub async fn run<R, F>(&self, f: F) -> R
+    where
+        R: Send + 'static,
+        F: FnOnce(&IOService<T>) -> R + Send + 'static,
+    {
+        finish(self.submit(f).await)
+    }
+
+    /// Async version of [`IOService::read_key`].
+    pub async fn read_key(&self, key: impl Into<SmcKey>) -> Result<SMCVal, SmcError> {
+        let key = key.into();
+        self.run(move |smc| smc.read_key(key)).await
+    }
+
+    /// Async version of [`IOService::write_key`].
+    ///
+    /// Dropping the future may or may not write the key, see
+    /// [Cancellation](self#cancellation).
+    pub async fn write_key(&self, key: impl Into<SmcKey>, value: &[u8]) -> Result<(), SmcError> {
+        let key = key.into();
+        let value = value.to_vec();
+        self.run(move |smc| smc.write_key(key, &value)).await
+    }
+
+    /// Async version of [`IOService::get_key_info`].
+    pub async fn get_key_info(
+        &self,
+        key: impl Into<SmcKey>,
+    ) -> Result<SMCKeyData_keyInfo, SmcError> {
+        let key = key.into();
+        self.run(move |smc| smc.get_key_info(key)).await
+    }
+
+    /// Returns a stream over all SMC keys and their values, the async version
+    /// of [`IOService::values_iter`].
+    ///
+    /// Every value is read by its own job, so other calls are not delayed
+    /// by a slow consumer of the stream.
+    pub async fn values_stream(&self) -> Result<ValStream<T>, SmcError> {
+        let total_count = self.run(|smc| smc.keys_count()).await?;
+        Ok(ValStream {
+            smc: self.clone(),
+            total_count,
+            current: 0,
+            pending: None,
+        })
+    }
+
+    /// Queues `f` on the worker thread.
+    fn submit<R, F>(&self, f: F) -> JobResult<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&IOService<T>) -> R + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job<T> = Box::new(move |smc| {
+            // the caller may be gone, its result is discarded then
+            let _ = tx.send(catch_unwind(AssertUnwindSafe(|| f(smc))));
+        });
+        // the worker runs as long as a sender exists, and never panics
+        self.jobs.send(job).expect("the SMC worker thread stopped");
+        rx
+    }
+}
+
+/// Returns the result of a job, or resumes its panic.
+fn finish<R>(result: Result<thread::Result<R>, oneshot::error::RecvError>) -> R {
+    match result.expect("the SMC worker thread dropped a job") {
+        Ok(r) => r,
+        Err(panic) => resume_unwind(panic),
+    }
+}
+
+impl<T> Clone for AsyncSmc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            jobs: self.jobs.clone(),
+        }
+    }
+}
+
+/// Stream over SMC key-value pairs.
+///
+/// This stream is created by [`AsyncSmc::values_stream`] and yields
+/// `Result<SMCVal, ValError>` for each key in the SMC, like [`ValIter`].
+pub struct ValStream<T> {
+    smc: AsyncSmc<T>,
+    total_count: u32,
+    current: u32,
+    pending: Option<JobResult<Option<Result<SMCVal, ValError>>>>,
+}
+
+impl<T: SmcTransport + Send + 'static> Stream for ValStream<T> {
+    type Item = Result<SMCVal, ValError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let pending = match &mut this.pending {
+            Some(pending) => pending,
+            None => {
+                if this.current >= this.total_count {
+                    return Poll::Ready(None);
+                }
+                let (current, total_count) = (this.current, this.total_count);
+                this.current += 1;
+                this.pending.insert(
+                    this.smc
+                        .submit(move |smc| ValIter::range(smc, current, total_count).next()),
+                )
+            }
+        };
+        let result = ready!(Pin::new(pending).poll(cx));
+        this.pending = None;
+        Poll::Ready(finish(result))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = usize::from(self.pending.is_some());
+        let len = self.total_count.saturating_sub(self.current) as usize + pending;
+        (len, Some(len))
+    }
+}
+
+#[test]
+fn async_smc() {
+    use crate::mock::MockSmc;
+    use std::future::poll_fn;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("the runtime starts");
+    runtime.block_on(async {
+        let smc = AsyncSmc::with_transport(MockSmc::sample());
+        let val = smc.read_key(b"TB0T").await.expect("TB0T exists");
+        assert_eq!(val.valid_bytes(), 25.8f32.to_le_bytes());
+        let info = smc.get_key_info(b"ACLC").await.expect("ACLC exists");
+        assert_eq!(info.data_size, 1);
+        assert_eq!(
+            smc.write_key(b"ACLC", &[1]).await,
+            Err(SmcError::PrivilegeViolation)
+        );
+        smc.run(|smc| smc.transport().set_privileged(true)).await;
+        assert_eq!(smc.write_key(b"ACLC", &[7]).await, Ok(()));
+        assert_eq!(smc.read_key(b"ACLC").await.map(|v| v.bytes[0]), Ok(7));
+
+        let mut stream = smc.values_stream().await.expect("#KEY is readable");
+        assert_eq!(stream.size_hint(), (8, Some(8)));
+        let mut keys = Vec::new();
+        while let Some(val) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            keys.push(val.expect("sample keys are readable").key);
+        }
+        assert_eq!(keys.len(), 8);
+        assert_eq!(keys[7], SmcKey::new(b"TB0T"));
+
+        let panicked = tokio::spawn({
+            let smc = smc.clone();
+            async move { smc.run(|_| panic!("job panicked")).await }
+        });
+        assert!(panicked.await.is_err_and(|e| e.is_panic()));
+        assert_eq!(smc.run(|smc| smc.keys_count()).await, Ok(8));
+    });
+}
@@ -0,0 +1,196 @@
+//! Handle shared between threads.
+//!
+//! [`SharedSmc`] is a cloneable [`IOService`] for multi-threaded programs.
+//! Its transport is guarded by a mutex, so only one SMC call is in flight at
+//! a time, while the key info cache and the configuration of the handle are
+//! shared by every clone.
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::structs::SMCKeyData;
+use crate::value::ByteOrder;
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A transport that serializes the calls to another transport.
+///
+/// Used by [`SharedSmc`], calls made from different threads wait for each
+/// other.
+#[derive(Debug, Default)]
+pub struct SerializedTransport<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> SerializedTransport<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            inner: Mutex::new(transport),
+        }
+    }
+
+    /// Locks the transport, no SMC call can be made until the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: SmcTransport> SmcTransport for SerializedTransport<T> {
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        self.lock().call(input, output)
+    }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.lock().byte_order()
+    }
+}
+
+/// A cloneable handle of the SMC, safe to use from many threads.
+///
+/// `SharedSmc` derefs to [`IOService`], so every SMC operation is available.
+/// Clones share one connection, `IOServiceClose` is called once, when the last clone is
+/// dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(target_os = "macos")] {
+/// use smc_lib::shared::SharedSmc;
+///
+/// let smc = SharedSmc::init().unwrap();
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let smc = smc.clone();
+///         std::thread::spawn(move || smc.read_key(b"TB0T").map(|v| v.data_value()))
+///     })
+///     .collect();
+/// for handle in handles {
+///     println!("{:?}", handle.join().unwrap());
+/// }
+/// # }
+/// ```
+#[cfg(target_os = "macos")]
+pub struct SharedSmc<T = crate::iokit::IOKitConnection> {
+    service: Arc<IOService<SerializedTransport<T>>>,
+}
+
+/// A cloneable handle of the SMC, safe to use from many threads.
+///
+/// `SharedSmc` derefs to [`IOService`], so every SMC operation is available.
+/// Clones share one transport, it is dropped once, when the last clone is
+/// dropped.
+#[cfg(not(target_os = "macos"))]
+pub struct SharedSmc<T> {
+    service: Arc<IOService<SerializedTransport<T>>>,
+}
+
+#[cfg(target_os = "macos")]
+impl SharedSmc {
+    /// Opens the AppleSMC service, see [`IOService::init`].
+    pub fn init() -> Result<Self, SmcError> {
+        IOService::init().map(Self::new)
+    }
+}
+
+impl<T: SmcTransport> SharedSmc<T> {
+    /// Shares `service`, its configuration and key info cache are kept.
+    pub fn new(service: IOService<T>) -> Self {
+        Self {
+            service: Arc::new(service.map_transport(SerializedTransport::new)),
+        }
+    }
+
+    /// Creates a shared handle that talks to a SMC through `transport`.
+    pub fn with_transport(transport: T) -> Self {
+        Self::new(IOService::with_transport(transport))
+    }
+}
+
+impl<T> Clone for SharedSmc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            service: Arc::clone(&self.service),
+        }
+    }
+}
+
+impl<T> Deref for SharedSmc<T> {
+    type Target = IOService<SerializedTransport<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.service
+    }
+}
+
+#[test]
+fn concurrent_readers() {
+    use crate::key::SmcKey;
+    use crate::mock::MockSmc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Fails if two calls overlap, counts how often it is dropped.
+    struct Exclusive {
+        smc: MockSmc,
+        in_call: AtomicBool,
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl SmcTransport for Exclusive {
+        fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+            assert!(!self.in_call.swap(true, Ordering::SeqCst), "calls overlap");
+            std::thread::yield_now();
+            let res = self.smc.call(input, output);
+            self.in_call.store(false, Ordering::SeqCst);
+            res
+        }
+
+        fn byte_order(&self) -> ByteOrder {
+            self.smc.byte_order()
+        }
+    }
+
+    impl Drop for Exclusive {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let smc = SharedSmc::new(
+        IOService::with_transport(Exclusive {
+            smc: MockSmc::sample(),
+            in_call: AtomicBool::new(false),
+            drops: Arc::clone(&drops),
+        })
+        .with_key_info_cache(),
+    );
+    smc.transport().lock().smc.set_privileged(true);
+    let threads: Vec<_> = (0..8u8)
+        .map(|i| {
+            let smc = smc.clone();
+            std::thread::spawn(move || {
+                for _ in 0..50 {
+                    let val = smc.read_key(b"TB0T").expect("TB0T exists");
+                    assert_eq!(val.valid_bytes(), 25.8f32.to_le_bytes());
+                    assert_eq!(smc.keys_count(), Ok(8));
+                    assert_eq!(smc.write_key(b"ACLC", &[i]), Ok(()));
+                    let keys: Vec<_> = smc
+                        .keys_iter()
+                        .expect("#KEY is readable")
+                        .without_info()
+                        .filter_map(Result::ok)
+                        .map(|e| e.key)
+                        .collect();
+                    assert_eq!(keys[7], SmcKey::new(b"TB0T"));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().expect("no reader panicked");
+    }
+    assert!(smc.read_key(b"ACLC").is_ok_and(|v| v.bytes[0] < 8));
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(smc);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,612 @@
+//! Read-only SMC replaying a saved dump.
+//!
+//! A [`SnapshotSmc`] is a [`SmcTransport`] answering from keys captured on
+//! another machine, so analysis tools and tests can run against real data
+//! through [`IOService`].
+//!
+//! With the `serde` feature, a snapshot is saved and loaded with any serde
+//! format. The dump records the byte order of the SMC, and for each key its
+//! info and its bytes in hex, e.g. in JSON:
+//!
+//! ```json
+//! {
+//!   "byte_order": "big",
+//!   "keys": [
+//!     { "key": "TB0T", "info": { "data_type": "sp78", "data_size": 2, "attributes": 128 }, "bytes": "1980" },
+//!     { "key": "zSPp", "info": { "data_type": "hex_", "data_size": 112, "attributes": 128 } },
+//!     { "key": "CHLS" }
+//!   ]
+//! }
+//! ```
+//!
+//! Keys whose value or key info could not be captured exist in the
+//! snapshot, but reading them fails. The error of the capture is kept with
+//! the code the SMC returned, e.g. `"error": { "kern": -536870207 }` for a
+//! privilege violation or `"error": { "result": 133 }` for a firmware
+//! result, and replayed.
+//!
+//! # Importing `smc list` output
+//!
+//! Captures taken with `smc list` (both stdout and stderr) can be parsed
+//! with [`FromStr`], one key per line:
+//!
+//! ```text
+//! TB0T flt  size: 4(bytes 60 66 ce 41) attrs: r------ value: 25.799988
+//! zSPp hex_ size: 112 index: 2146, error: (iokit/common) invalid argument
+//! CHLS index: 375, error: (iokit/common) privilege violation
+//! ```
+//!
+//! Anything after the bytes and the attributes, and the error message, are
+//! ignored, reading the keys with an error fails with
+//! `SmcError::Transport(KERN_FAILURE)`. The output does not tell the byte
+//! order, it must be set with [`SnapshotSmc::with_byte_order`] for captures
+//! of Intel Macs.
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::io::IOService;
+//! use smc_lib::snapshot::SnapshotSmc;
+//!
+//! let dump = "TB0T flt  size: 4(bytes 60 66 ce 41) value: 25.799988\n";
+//! let smc = IOService::with_transport(dump.parse::<SnapshotSmc>().unwrap());
+//! assert_eq!(smc.read_key(b"TB0T").unwrap().valid_bytes(), [0x60, 0x66, 0xce, 0x41]);
+//! ```
+
+#[cfg(feature = "serde")]
+use crate::io::{IO_RETURN_NOT_PRIVILEGED, kern_return_t};
+use crate::io::{IOService, KERN_FAILURE, KERN_NOT_SUPPORTED, SmcError, SmcTransport};
+use crate::key::{KeyAttributes, SmcKey};
+use crate::structs::{
+    SMC_ATTR_READ, SMC_BYTES_LEN, SMC_CMD_READ_BYTES, SMC_CMD_READ_INDEX, SMC_CMD_READ_KEYINFO,
+    SMC_CMD_WRITE_BYTES, SMC_RESULT_BAD_COMMAND, SMC_RESULT_KEY_INDEX_RANGE_ERROR,
+    SMC_RESULT_KEY_NOT_FOUND, SMC_RESULT_KEY_SIZE_MISMATCH, SMCBytes, SMCKeyData,
+    SMCKeyData_keyInfo, SMCVal,
+};
+use crate::value::ByteOrder;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// A key stored in [`SnapshotSmc`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SnapshotKey {
+    /// `None` if the key info could not be read when capturing
+    pub info: Option<SMCKeyData_keyInfo>,
+    /// `None` if the value could not be read when capturing
+    pub bytes: Option<SMCBytes>,
+    /// The error of reading the key when capturing, returned for the key
+    /// info or the value that is `None`. Without it, reading them fails with
+    /// `SmcError::Transport(KERN_FAILURE)`.
+    pub error: Option<SmcError>,
+}
+
+impl SnapshotKey {
+    /// Returns the capture error the way the SMC reported it, as the
+    /// `result` of `output` or as the error of the call.
+    fn replay_error(&self, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        match self.error {
+            Some(SmcError::KeyNotFound) => output.result = SMC_RESULT_KEY_NOT_FOUND,
+            Some(SmcError::Firmware(err)) => output.result = err.code(),
+            Some(err) => return Err(err),
+            None => return Err(SmcError::Transport(KERN_FAILURE)),
+        }
+        Ok(())
+    }
+}
+
+impl From<&SMCVal> for SnapshotKey {
+    fn from(val: &SMCVal) -> Self {
+        Self {
+            info: Some(SMCKeyData_keyInfo {
+                data_size: val.data_size,
+                data_type: u32::from(val.data_type),
+                data_attributes: val.attributes.bits(),
+            }),
+            bytes: Some(val.bytes),
+            error: None,
+        }
+    }
+}
+
+/// A read-only SMC answering from a saved key table.
+///
+/// Like the firmware, the table is sorted and `#KEY` holds the number of
+/// keys, it is maintained by the snapshot. Writes fail with
+/// `SmcError::Transport(KERN_NOT_SUPPORTED)`.
+///
+/// A snapshot is created by [`capture`](Self::capture), by loading a serde
+/// dump, by parsing `smc list` output, or by inserting keys. See the
+/// [module docs](self) for the formats.
+///
+/// New and parsed snapshots are little endian (Apple Silicon), see
+/// [`with_byte_order`](Self::with_byte_order).
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "Dump", try_from = "Dump")
+)]
+pub struct SnapshotSmc {
+    keys: BTreeMap<SmcKey, SnapshotKey>,
+    byte_order: ByteOrder,
+}
+
+impl Default for SnapshotSmc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnapshotSmc {
+    /// Creates a snapshot whose table only contains `#KEY`.
+    pub fn new() -> Self {
+        let mut snapshot = Self {
+            keys: BTreeMap::new(),
+            byte_order: ByteOrder::Little,
+        };
+        snapshot.update_count();
+        snapshot
+    }
+
+    /// Sets the byte order of the SMC the snapshot was taken from.
+    pub fn with_byte_order(mut self, order: ByteOrder) -> Self {
+        self.byte_order = order;
+        self
+    }
+
+    /// Reads every key of a SMC into a snapshot.
+    ///
+    /// Keys whose value can not be read are kept with their key info, as
+    /// returned by [`IOService::get_key_info`], and the error of the read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keys can not be enumerated.
+    pub fn capture<T: SmcTransport>(smc: &IOService<T>) -> Result<Self, SmcError> {
+        let mut snapshot = Self::new().with_byte_order(smc.byte_order());
+        for val in smc.values_iter()? {
+            match val {
+                Ok(val) => snapshot.insert(val.key, SnapshotKey::from(&val)),
+                Err(err) => {
+                    let Some(key) = err.key else {
+                        continue;
+                    };
+                    let entry = SnapshotKey {
+                        info: smc.get_key_info(key).ok(),
+                        bytes: None,
+                        error: Some(err.error),
+                    };
+                    snapshot.insert(key, entry);
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Inserts or replaces a key.
+    ///
+    /// `#KEY` is maintained by the snapshot, inserting it has no effect.
+    pub fn insert(&mut self, key: impl Into<SmcKey>, value: SnapshotKey) {
+        self.keys.insert(key.into(), value);
+        self.update_count();
+    }
+
+    /// Returns a key of the snapshot.
+    pub fn get(&self, key: impl Into<SmcKey>) -> Option<&SnapshotKey> {
+        self.keys.get(&key.into())
+    }
+
+    fn update_count(&mut self) {
+        self.keys.remove(&SmcKey::new(b"#KEY"));
+        let count = self.keys.len() as u32 + 1;
+        let mut bytes = SMCBytes::default();
+        bytes[..4].copy_from_slice(&count.to_be_bytes());
+        self.keys.insert(
+            SmcKey::new(b"#KEY"),
+            SnapshotKey {
+                info: Some(SMCKeyData_keyInfo {
+                    data_size: 4,
+                    data_type: u32::from_be_bytes(*b"ui32"),
+                    data_attributes: SMC_ATTR_READ,
+                }),
+                bytes: Some(bytes),
+                error: None,
+            },
+        );
+    }
+}
+
+impl SmcTransport for SnapshotSmc {
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        *output = SMCKeyData::default();
+        if input.data8 == SMC_CMD_READ_INDEX {
+            match self.keys.keys().nth(input.data32 as usize) {
+                Some(key) => output.key = key.to_u32(),
+                None => output.result = SMC_RESULT_KEY_INDEX_RANGE_ERROR,
+            }
+            return Ok(());
+        }
+        if input.data8 == SMC_CMD_WRITE_BYTES {
+            return Err(SmcError::Transport(KERN_NOT_SUPPORTED));
+        }
+        let Some(entry) = self.keys.get(&SmcKey::from(input.key)) else {
+            output.result = match input.data8 {
+                SMC_CMD_READ_KEYINFO | SMC_CMD_READ_BYTES => SMC_RESULT_KEY_NOT_FOUND,
+                _ => SMC_RESULT_BAD_COMMAND,
+            };
+            return Ok(());
+        };
+        match input.data8 {
+            SMC_CMD_READ_KEYINFO => match entry.info {
+                Some(info) => output.key_info = info,
+                None => entry.replay_error(output)?,
+            },
+            SMC_CMD_READ_BYTES => match entry.info.zip(entry.bytes) {
+                Some((info, _)) if input.key_info.data_size != info.data_size => {
+                    output.result = SMC_RESULT_KEY_SIZE_MISMATCH
+                }
+                Some((_, bytes)) => output.bytes = bytes,
+                None => entry.replay_error(output)?,
+            },
+            _ => output.result = SMC_RESULT_BAD_COMMAND,
+        }
+        Ok(())
+    }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+}
+
+/// The serde representation of a [`SnapshotSmc`], without `#KEY`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Dump {
+    byte_order: ByteOrder,
+    keys: Vec<DumpKey>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DumpKey {
+    key: SmcKey,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info: Option<DumpInfo>,
+    /// The valid bytes in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<DumpError>,
+}
+
+/// A capture error, as the code the SMC returned.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DumpError {
+    /// Code of a failed kernel or IOKit call, see [`SmcError::from_kern`]
+    Kern(kern_return_t),
+    /// `result` byte of the SMC, see [`SmcError::from_result`]
+    Result(u8),
+}
+
+#[cfg(feature = "serde")]
+impl From<SmcError> for DumpError {
+    fn from(err: SmcError) -> Self {
+        match err {
+            SmcError::KeyNotFound => DumpError::Result(SMC_RESULT_KEY_NOT_FOUND),
+            SmcError::Firmware(err) => DumpError::Result(err.code()),
+            SmcError::PrivilegeViolation => DumpError::Kern(IO_RETURN_NOT_PRIVILEGED),
+            SmcError::Transport(code) | SmcError::OpenFailed(code) => DumpError::Kern(code),
+            // errors of the library have no code, they are not returned by reads
+            _ => DumpError::Kern(KERN_FAILURE),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<DumpError> for SmcError {
+    fn from(err: DumpError) -> Self {
+        match err {
+            DumpError::Kern(code) => SmcError::from_kern(code),
+            DumpError::Result(result) => {
+                SmcError::from_result(result).unwrap_or(SmcError::Transport(KERN_FAILURE))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DumpInfo {
+    data_type: String,
+    data_size: u32,
+    attributes: u8,
+}
+
+#[cfg(feature = "serde")]
+impl From<SnapshotSmc> for Dump {
+    fn from(snapshot: SnapshotSmc) -> Self {
+        let keys = snapshot
+            .keys
+            .into_iter()
+            .filter(|(key, _)| *key != SmcKey::new(b"#KEY"))
+            .map(|(key, entry)| DumpKey {
+                key,
+                info: entry.info.map(|info| DumpInfo {
+                    data_type: info.data_type().to_string(),
+                    data_size: info.data_size,
+                    attributes: info.data_attributes,
+                }),
+                bytes: entry.info.zip(entry.bytes).map(|(info, bytes)| {
+                    let size = std::cmp::min(info.data_size as usize, SMC_BYTES_LEN);
+                    bytes[..size].iter().map(|b| format!("{:02x}", b)).collect()
+                }),
+                error: entry.error.map(DumpError::from),
+            })
+            .collect();
+        Dump {
+            byte_order: snapshot.byte_order,
+            keys,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<Dump> for SnapshotSmc {
+    type Error = String;
+
+    fn try_from(dump: Dump) -> Result<Self, Self::Error> {
+        let mut snapshot = Self::new().with_byte_order(dump.byte_order);
+        for DumpKey {
+            key,
+            info,
+            bytes,
+            error,
+        } in dump.keys
+        {
+            let info = info
+                .map(|info| {
+                    let data_type = <[u8; 4]>::try_from(info.data_type.as_bytes())
+                        .map_err(|_| format!("{}: the data type is not 4 bytes", key))?;
+                    Ok::<_, String>(SMCKeyData_keyInfo {
+                        data_size: info.data_size,
+                        data_type: u32::from_be_bytes(data_type),
+                        data_attributes: info.attributes,
+                    })
+                })
+                .transpose()?;
+            let bytes = match (info, bytes) {
+                (_, None) => None,
+                (None, Some(_)) => return Err(format!("{}: bytes without key info", key)),
+                (Some(info), Some(hex)) => {
+                    let mut bytes = SMCBytes::default();
+                    let size = std::cmp::min(info.data_size as usize, SMC_BYTES_LEN);
+                    if hex.len() != size * 2 {
+                        return Err(format!("{}: size does not match the bytes", key));
+                    }
+                    for (b, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+                        *b = std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(|| format!("{}: invalid byte", key))?;
+                    }
+                    Some(bytes)
+                }
+            };
+            let error = error.map(SmcError::from);
+            snapshot.insert(key, SnapshotKey { info, bytes, error });
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Error returned when `smc list` output can not be parsed into a
+/// [`SnapshotSmc`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotError {
+    /// 1-based line number
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl FromStr for SnapshotSmc {
+    type Err = SnapshotError;
+
+    /// Parses the output of `smc list`, see the [module docs](self#importing-smc-list-output).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut snapshot = Self::new();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line == "no data" {
+                continue;
+            }
+            let (key, entry) = parse_line(line).map_err(|reason| SnapshotError {
+                line: index + 1,
+                reason,
+            })?;
+            snapshot.insert(key, entry);
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Parses `KKKK[ TTTT size: N](bytes ..)[ attrs: A]` or `... index: I, error: E`.
+fn parse_line(line: &str) -> Result<(SmcKey, SnapshotKey), &'static str> {
+    // keys and types are fixed width, and may contain spaces
+    let key = line.as_bytes().get(..4).ok_or("missing key")?;
+    let key = SmcKey::from(<[u8; 4]>::try_from(key).map_err(|_| "missing key")?);
+    let rest = line.get(4..).ok_or("invalid key")?;
+    if rest.starts_with(" index: ") {
+        return Ok((key, SnapshotKey::default()));
+    }
+    let data_type = rest.as_bytes().get(1..5).ok_or("missing data type")?;
+    let data_type: [u8; 4] = data_type.try_into().map_err(|_| "missing data type")?;
+    let rest = rest
+        .get(5..)
+        .and_then(|r| r.strip_prefix(" size: "))
+        .ok_or("missing size")?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let data_size = rest[..digits].parse().map_err(|_| "invalid size")?;
+    let mut info = SMCKeyData_keyInfo {
+        data_size,
+        data_type: u32::from_be_bytes(data_type),
+        data_attributes: SMC_ATTR_READ,
+    };
+    let rest = &rest[digits..];
+    if rest.starts_with(" index: ") {
+        let entry = SnapshotKey {
+            info: Some(info),
+            ..SnapshotKey::default()
+        };
+        return Ok((key, entry));
+    }
+    let (hex, rest) = rest
+        .strip_prefix("(bytes")
+        .and_then(|r| r.split_once(')'))
+        .ok_or("missing bytes")?;
+    // dumps of older versions have no attributes
+    if let Some(attrs) = rest
+        .strip_prefix(" attrs: ")
+        .and_then(|r| r.get(..7))
+        .and_then(KeyAttributes::parse)
+    {
+        info.data_attributes = attrs.bits();
+    }
+    let mut bytes = SMCBytes::default();
+    let mut len = 0;
+    for b in hex.split_whitespace() {
+        let slot = bytes.get_mut(len).ok_or("too many bytes")?;
+        *slot = u8::from_str_radix(b, 16).map_err(|_| "invalid byte")?;
+        len += 1;
+    }
+    if len != std::cmp::min(data_size as usize, SMC_BYTES_LEN) {
+        return Err("size does not match the bytes");
+    }
+    Ok((
+        key,
+        SnapshotKey {
+            info: Some(info),
+            bytes: Some(bytes),
+            error: None,
+        },
+    ))
+}
+
+#[test]
+fn replay_smc_list() -> Result<(), SnapshotError> {
+    let dump = "\
+CHLS index: 375, error: (iokit/common) privilege violation
+FS!  ui16 size: 2(bytes 00 00) attrs: rw----- value: 0 (forced fans, one bit per fan)
+TB0T flt  size: 4(bytes 60 66 ce 41) value: le=25.799988, be=66525208000000000000
+zSPp hex_ size: 112 index: 2146, error: (iokit/common) invalid argument
+";
+    let smc = IOService::with_transport(dump.parse::<SnapshotSmc>()?);
+    assert_eq!(smc.keys_count(), Ok(5));
+    let val = smc.read_key(b"TB0T").map(|v| v.valid_bytes().to_vec());
+    assert_eq!(val, Ok(vec![0x60, 0x66, 0xce, 0x41]));
+    assert_eq!(smc.read_key(b"FS! ").map(|v| v.data_size), Ok(2));
+    assert_eq!(smc.get_key_info(b"zSPp").map(|i| i.data_size), Ok(112));
+    let not_captured = Err(SmcError::Transport(KERN_FAILURE));
+    assert_eq!(smc.read_key(b"zSPp").map(|_| ()), not_captured);
+    assert_eq!(smc.get_key_info(b"CHLS").map(|_| ()), not_captured);
+    assert_eq!(
+        smc.read_key(b"TA0P").map(|_| ()),
+        Err(SmcError::KeyNotFound)
+    );
+    let unsupported = Err(SmcError::Transport(KERN_NOT_SUPPORTED));
+    assert_eq!(smc.write_key(b"FS! ", &[0, 1]), unsupported);
+    assert_eq!(smc.values_iter().map(|i| i.count()), Ok(5));
+
+    let err = "TB0T flt  size: 4(bytes 60 66)".parse::<SnapshotSmc>();
+    assert_eq!(err.map(|_| ()).map_err(|e| e.line), Err(1));
+    Ok(())
+}
+
+#[test]
+fn capture() -> Result<(), SmcError> {
+    let live = IOService::with_transport(crate::mock::MockSmc::sample());
+    let replay = IOService::with_transport(SnapshotSmc::capture(&live)?);
+    assert_eq!(replay.keys_count(), live.keys_count());
+    for key in [b"ACLC", b"B0CT", b"RPlt", b"TB0T"] {
+        let expected = live.read_key(key).map(|v| (v.bytes, v.attributes));
+        assert_eq!(
+            replay.read_key(key).map(|v| (v.bytes, v.attributes)),
+            expected
+        );
+    }
+    // the private key keeps its key info and replays the read error
+    let chls = replay.transport().get(b"CHLS").map(|k| (k.bytes, k.error));
+    assert_eq!(chls, Some((None, Some(SmcError::PrivilegeViolation))));
+    let info = |i: SMCKeyData_keyInfo| (i.data_type(), i.data_size, i.data_attributes);
+    assert_eq!(
+        replay.get_key_info(b"CHLS").map(info),
+        live.get_key_info(b"CHLS").map(info)
+    );
+    assert_eq!(
+        replay.read_key(b"CHLS").map(|_| ()),
+        Err(SmcError::PrivilegeViolation)
+    );
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_dump() -> Result<(), serde_json::Error> {
+    let dump = r#"{
+        "byte_order": "big",
+        "keys": [
+            { "key": "CHLS" },
+            { "key": "F0Ac", "info": { "data_type": "fpe2", "data_size": 2, "attributes": 128 }, "bytes": "12c0" },
+            { "key": "zSPp", "info": { "data_type": "hex_", "data_size": 112, "attributes": 128 }, "error": { "result": 133 } }
+        ]
+    }"#;
+    let snapshot: SnapshotSmc = serde_json::from_str(dump)?;
+    let smc = IOService::with_transport(snapshot.clone());
+    assert_eq!(smc.byte_order(), ByteOrder::Big);
+    assert_eq!(smc.keys_count(), Ok(4));
+    // 0x12c0 is 1200 rpm in big endian fpe2
+    let rpm = smc
+        .read_key(b"F0Ac")
+        .map(|v| v.data_value().and_then(|v| v.as_f64()));
+    assert_eq!(rpm, Ok(Some(1200.0)));
+    assert_eq!(smc.get_key_info(b"zSPp").map(|i| i.data_size), Ok(112));
+    assert_eq!(
+        smc.read_key(b"zSPp").map(|_| ()),
+        Err(SmcError::Firmware(crate::io::FirmwareError::KeyNotReadable))
+    );
+    assert_eq!(
+        smc.get_key_info(b"CHLS").map(|_| ()),
+        Err(SmcError::Transport(KERN_FAILURE))
+    );
+
+    let saved = serde_json::to_string(&snapshot)?;
+    let loaded: SnapshotSmc = serde_json::from_str(&saved)?;
+    assert_eq!(loaded.byte_order, ByteOrder::Big);
+    assert_eq!(loaded.keys.len(), 4);
+    assert_eq!(
+        loaded
+            .get(b"F0Ac")
+            .and_then(|k| k.bytes)
+            .map(|b| b[..2] == [0x12, 0xc0]),
+        Some(true)
+    );
+    assert_eq!(
+        loaded.get(b"zSPp").and_then(|k| k.error),
+        Some(SmcError::Firmware(crate::io::FirmwareError::KeyNotReadable))
+    );
+
+    let bad = r#"{ "byte_order": "big", "keys": [{ "key": "F0Ac", "info": { "data_type": "fpe2", "data_size": 2, "attributes": 128 }, "bytes": "12" }] }"#;
+    let err = serde_json::from_str::<SnapshotSmc>(bad).map(|_| ());
+    assert!(err.is_err_and(|e| e.to_string().contains("size does not match")));
+    Ok(())
+}
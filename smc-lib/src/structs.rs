@@ -1,24 +1,92 @@
+use crate::{
+    data_type::DataType,
+    key::{KeyAttributes, SmcKey},
+    value::ByteOrder,
+};
 use std::ffi::c_char;
 
-pub(crate) const KERNEL_INDEX_SMC: u32 = 2;
-pub(crate) const SMC_CMD_READ_BYTES: u8 = 5;
-pub(crate) const SMC_CMD_READ_INDEX: u8 = 8;
-pub(crate) const SMC_CMD_READ_KEYINFO: u8 = 9;
-pub(crate) const SMC_CMD_WRITE_BYTES: u8 = 6;
+/// Selector of the AppleSMC user client method that exchanges [`SMCKeyData`].
+pub const KERNEL_INDEX_SMC: u32 = 2;
+/// `data8` command: read the value bytes of `key`.
+pub const SMC_CMD_READ_BYTES: u8 = 5;
+/// `data8` command: look up the key at index `data32`.
+pub const SMC_CMD_READ_INDEX: u8 = 8;
+/// `data8` command: read the [`SMCKeyData_keyInfo`] of `key`.
+pub const SMC_CMD_READ_KEYINFO: u8 = 9;
+/// `data8` command: write `bytes` to `key`.
+pub const SMC_CMD_WRITE_BYTES: u8 = 6;
+
+/// `result` byte: the command succeeded.
+pub const SMC_RESULT_SUCCESS: u8 = 0;
+/// `result` byte: unspecified error.
+pub const SMC_RESULT_ERROR: u8 = 0x01;
+/// `result` byte: another command was in progress.
+pub const SMC_RESULT_COMM_COLLISION: u8 = 0x80;
+/// `result` byte: unexpected data was received.
+pub const SMC_RESULT_SPURIOUS_DATA: u8 = 0x81;
+/// `result` byte: the command in `data8` is unknown.
+pub const SMC_RESULT_BAD_COMMAND: u8 = 0x82;
+/// `result` byte: a parameter of the command is invalid.
+pub const SMC_RESULT_BAD_PARAMETER: u8 = 0x83;
+/// `result` byte: the key does not exist.
+pub const SMC_RESULT_KEY_NOT_FOUND: u8 = 0x84;
+/// `result` byte: the key can not be read.
+pub const SMC_RESULT_NOT_READABLE: u8 = 0x85;
+/// `result` byte: the key can not be written.
+pub const SMC_RESULT_NOT_WRITABLE: u8 = 0x86;
+/// `result` byte: the size of the request does not match the key.
+pub const SMC_RESULT_KEY_SIZE_MISMATCH: u8 = 0x87;
+/// `result` byte: the request is malformed.
+pub const SMC_RESULT_FRAMING_ERROR: u8 = 0x88;
+/// `result` byte: an argument of the command is invalid.
+pub const SMC_RESULT_BAD_ARGUMENT: u8 = 0x89;
+/// `result` byte: the command timed out.
+pub const SMC_RESULT_TIMEOUT: u8 = 0xb7;
+/// `result` byte: the index is out of the key table.
+pub const SMC_RESULT_KEY_INDEX_RANGE_ERROR: u8 = 0xb8;
+/// `result` byte: a parameter of a function key is invalid.
+pub const SMC_RESULT_BAD_FUNC_PARAMETER: u8 = 0xc0;
+/// `result` byte: the event buffer was read out of order.
+pub const SMC_RESULT_EVENT_BUFF_WRONG_ORDER: u8 = 0xc4;
+/// `result` byte: the event buffer can not be read.
+pub const SMC_RESULT_EVENT_BUFF_READ_ERROR: u8 = 0xc5;
+/// `result` byte: a device behind the SMC can not be accessed.
+pub const SMC_RESULT_DEVICE_ACCESS_ERROR: u8 = 0xc7;
+/// `result` byte: the feature is not supported.
+pub const SMC_RESULT_UNSUPPORTED_FEATURE: u8 = 0xcb;
+/// `result` byte: the SMBus can not be accessed.
+pub const SMC_RESULT_SMB_ACCESS_ERROR: u8 = 0xcc;
+/// `result` byte: the size is invalid.
+pub const SMC_RESULT_INVALID_SIZE: u8 = 0xce;
+
+/// `data_attributes` bit: writing the key needs privilege.
+pub const SMC_ATTR_PRIVATE_WRITE: u8 = 0x01;
+/// `data_attributes` bit: reading the key needs privilege.
+pub const SMC_ATTR_PRIVATE_READ: u8 = 0x02;
+/// `data_attributes` bit: the key is read and written atomically.
+pub const SMC_ATTR_ATOMIC: u8 = 0x04;
+/// `data_attributes` bit: the value of the key never changes.
+pub const SMC_ATTR_CONST: u8 = 0x08;
+/// `data_attributes` bit: the key is a function, writing it runs a command.
+pub const SMC_ATTR_FUNCTION: u8 = 0x10;
+/// `data_attributes` bit: the key can be written.
+pub const SMC_ATTR_WRITE: u8 = 0x40;
+/// `data_attributes` bit: the key can be read.
+pub const SMC_ATTR_READ: u8 = 0x80;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
-pub(crate) struct SMCKeyData_vers {
-    major: c_char,
-    minor: c_char,
-    build: c_char,
-    reserved: [c_char; 1],
-    release: u16,
+pub struct SMCKeyData_vers {
+    pub major: c_char,
+    pub minor: c_char,
+    pub build: c_char,
+    pub reserved: [c_char; 1],
+    pub release: u16,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
-pub(crate) struct SMCKeyData_plimitData {
+pub struct SMCKeyData_plimitData {
     pub version: u16,
     pub length: u16,
     pub cpu_plimit: u32,
@@ -34,14 +102,23 @@ pub(crate) struct SMCKeyData_plimitData {
 #[derive(Clone, Copy, Debug, Default)]
 pub struct SMCKeyData_keyInfo {
     pub data_size: u32,
-    /// the data type
-    ///
-    /// convert it to human readable str by
-    /// `String::from_utf8_lossy(&data_type.to_be_bytes())`
+    /// the data type as big endian `u32`, see [`data_type()`](Self::data_type)
     pub data_type: u32,
     pub data_attributes: u8,
 }
 
+impl SMCKeyData_keyInfo {
+    /// Returns the data type of the key.
+    pub fn data_type(&self) -> DataType {
+        DataType::from(self.data_type)
+    }
+
+    /// Returns the attributes of the key.
+    pub fn attributes(&self) -> KeyAttributes {
+        KeyAttributes::from_bits(self.data_attributes)
+    }
+}
+
 /// Byte array type for SMC data.
 ///
 /// SMC values are stored as byte arrays with a maximum length of [`SMC_BYTES_LEN`].
@@ -50,9 +127,14 @@ pub type SMCBytes = [u8; SMC_BYTES_LEN];
 /// Maximum size in bytes for SMC data.
 pub const SMC_BYTES_LEN: usize = 32;
 
+/// The struct exchanged with the SMC for every command.
+///
+/// The command is selected by `data8`, see the `SMC_CMD_*` constants.
+/// Implementors of [`crate::io::SmcTransport`] receive a filled request and
+/// write the firmware's answer into a second instance of this struct.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
-pub(crate) struct SMCKeyData {
+pub struct SMCKeyData {
     pub key: u32,
     pub vers: SMCKeyData_vers,
     pub plimit_data: SMCKeyData_plimitData,
@@ -73,6 +155,7 @@ pub(crate) struct SMCKeyData {
 /// # Example
 ///
 /// ```no_run
+/// # #[cfg(target_os = "macos")] {
 /// use smc_lib::io::IOService;
 ///
 /// let smc = IOService::init().unwrap();
@@ -85,11 +168,15 @@ pub(crate) struct SMCKeyData {
 /// if let Some(parsed) = val.data_value() {
 ///     println!("Parsed value: {}", parsed);
 /// }
+/// # }
 /// ```
 #[derive(Clone, Copy, Debug, Default)]
 pub struct SMCVal {
-    pub key: [u8; 4],
+    pub key: SmcKey,
     pub data_size: u32,
-    pub data_type: [u8; 4],
+    pub data_type: DataType,
+    pub attributes: KeyAttributes,
     pub bytes: SMCBytes,
+    /// The byte order used by [`SMCVal::data_value`]
+    pub byte_order: ByteOrder,
 }
@@ -0,0 +1,368 @@
+//! Transport for the Linux `applesmc` driver, used on Intel Macs running Linux.
+//!
+//! The driver does not allow to read a key by its name, it only exposes the
+//! key at a selected index:
+//!
+//! - `key_count` - the number of keys
+//! - `key_at_index` - write an index here to select a key
+//! - `key_at_index_name`, `key_at_index_type` - name and type of the selected key
+//! - `key_at_index_data` - raw bytes of the selected key
+//!
+//! Keys are looked up by a binary search over the sorted key table.
+//! Writing keys is not supported.
+
+use crate::io::{KERN_FAILURE, KERN_NOT_SUPPORTED, SmcError, SmcTransport};
+use crate::structs::{
+    SMC_ATTR_READ, SMC_BYTES_LEN, SMC_CMD_READ_BYTES, SMC_CMD_READ_INDEX, SMC_CMD_READ_KEYINFO,
+    SMC_CMD_WRITE_BYTES, SMC_RESULT_BAD_COMMAND, SMC_RESULT_KEY_INDEX_RANGE_ERROR,
+    SMC_RESULT_KEY_NOT_FOUND, SMC_RESULT_KEY_SIZE_MISMATCH, SMCKeyData, SMCKeyData_keyInfo,
+};
+use crate::value::ByteOrder;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+/// Default location of the `applesmc` device in sysfs.
+pub const APPLESMC_ROOT: &str = "/sys/devices/platform/applesmc.768";
+
+/// Access to the files of the driver, faked in the tests.
+trait Device: fmt::Debug + Send + Sync {
+    fn read(&self, file: &str) -> io::Result<Vec<u8>>;
+
+    fn write(&self, file: &str, contents: &str) -> io::Result<()>;
+}
+
+/// The files of the driver in a sysfs directory.
+#[derive(Debug)]
+struct Sysfs(PathBuf);
+
+impl Device for Sysfs {
+    fn read(&self, file: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.0.join(file))
+    }
+
+    fn write(&self, file: &str, contents: &str) -> io::Result<()> {
+        fs::write(self.0.join(file), contents)
+    }
+}
+
+/// State of the driver, guarded by the lock of [`SysfsSmc`].
+#[derive(Debug, Default)]
+struct State {
+    /// Names of the keys by index, `None` until read
+    names: Vec<Option<[u8; 4]>>,
+    /// Index written to `key_at_index` during the current call
+    selected: Option<u32>,
+}
+
+/// Connection to the SMC through the `applesmc` sysfs files.
+///
+/// Selecting a key changes the state of the driver, so the transport
+/// serializes its calls. Selecting needs root privilege.
+///
+/// The names of the keys are cached as the binary searches read them, so
+/// that a lookup only reads the names it has not seen before, and then
+/// selects the key. The cache is dropped when `key_count` changes.
+///
+/// # Example
+///
+/// ```no_run
+/// use smc_lib::io::IOService;
+/// use smc_lib::sysfs::SysfsSmc;
+///
+/// let smc = IOService::with_transport(SysfsSmc::open().unwrap());
+/// println!("{}", smc.read_key(b"TC0P").unwrap());
+/// ```
+#[derive(Debug)]
+pub struct SysfsSmc {
+    root: PathBuf,
+    device: Box<dyn Device>,
+    state: Mutex<State>,
+}
+
+impl SysfsSmc {
+    /// Opens the `applesmc` device at [`APPLESMC_ROOT`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `key_count` can not be read, usually because the
+    /// driver is not loaded.
+    pub fn open() -> io::Result<Self> {
+        Self::open_at(APPLESMC_ROOT)
+    }
+
+    /// Opens an `applesmc` device, or a fake one, at `root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root/key_count` can not be read.
+    pub fn open_at(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let device = Box::new(Sysfs(root.clone()));
+        Self::with_device(root, device)
+    }
+
+    fn with_device(root: PathBuf, device: Box<dyn Device>) -> io::Result<Self> {
+        let smc = Self {
+            root,
+            device,
+            state: Mutex::new(State::default()),
+        };
+        smc.key_count()?;
+        Ok(smc)
+    }
+
+    /// Returns the sysfs directory of the device.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_text(&self, file: &str) -> io::Result<String> {
+        let mut text = String::from_utf8_lossy(&self.device.read(file)?).into_owned();
+        if text.ends_with('\n') {
+            text.pop();
+        }
+        Ok(text)
+    }
+
+    fn key_count(&self) -> io::Result<u32> {
+        self.read_text("key_count")?
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Selects the key at `index`, unless the current call selected it.
+    fn select(&self, state: &mut State, index: u32) -> io::Result<()> {
+        if state.selected != Some(index) {
+            state.selected = None;
+            self.device.write("key_at_index", &index.to_string())?;
+            state.selected = Some(index);
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the key at `index`, selecting it if it is not
+    /// cached.
+    fn name_at(&self, state: &mut State, index: u32) -> io::Result<[u8; 4]> {
+        if let Some(Some(name)) = state.names.get(index as usize) {
+            return Ok(*name);
+        }
+        self.select(state, index)?;
+        let name = four_cc(&self.read_text("key_at_index_name")?);
+        if let Some(slot) = state.names.get_mut(index as usize) {
+            *slot = Some(name);
+        }
+        Ok(name)
+    }
+
+    /// Resets the name cache if the number of keys changed, returns it.
+    fn sync_count(&self, state: &mut State) -> io::Result<u32> {
+        let count = self.key_count()?;
+        if state.names.len() != count as usize {
+            state.names = vec![None; count as usize];
+        }
+        Ok(count)
+    }
+
+    /// Selects `key`, returning `None` if it does not exist.
+    fn find(&self, state: &mut State, key: &[u8; 4]) -> io::Result<Option<u32>> {
+        let key = u32::from_be_bytes(*key);
+        let (mut low, mut high) = (0, self.sync_count(state)?);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let name = u32::from_be_bytes(self.name_at(state, mid)?);
+            match name.cmp(&key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    self.select(state, mid)?;
+                    return Ok(Some(mid));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn exchange(
+        &self,
+        state: &mut State,
+        input: &SMCKeyData,
+        output: &mut SMCKeyData,
+    ) -> io::Result<()> {
+        match input.data8 {
+            SMC_CMD_READ_INDEX => {
+                if input.data32 < self.sync_count(state)? {
+                    output.key = u32::from_be_bytes(self.name_at(state, input.data32)?);
+                } else {
+                    output.result = SMC_RESULT_KEY_INDEX_RANGE_ERROR;
+                }
+            }
+            SMC_CMD_READ_KEYINFO | SMC_CMD_READ_BYTES => {
+                let key = input.key.to_be_bytes();
+                let (data_type, data) = if &key == b"#KEY" {
+                    (*b"ui32", self.key_count()?.to_be_bytes().to_vec())
+                } else if self.find(state, &key)?.is_some() {
+                    let data_type = four_cc(&self.read_text("key_at_index_type")?);
+                    (data_type, self.device.read("key_at_index_data")?)
+                } else {
+                    output.result = SMC_RESULT_KEY_NOT_FOUND;
+                    return Ok(());
+                };
+                if data.len() > SMC_BYTES_LEN {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "SMC value is too long",
+                    ));
+                }
+                if input.data8 == SMC_CMD_READ_KEYINFO {
+                    output.key_info = SMCKeyData_keyInfo {
+                        data_size: data.len() as u32,
+                        data_type: u32::from_be_bytes(data_type),
+                        data_attributes: SMC_ATTR_READ,
+                    };
+                } else if input.key_info.data_size != data.len() as u32 {
+                    output.result = SMC_RESULT_KEY_SIZE_MISMATCH;
+                } else {
+                    output.bytes[..data.len()].copy_from_slice(&data);
+                }
+            }
+            _ => output.result = SMC_RESULT_BAD_COMMAND,
+        }
+        Ok(())
+    }
+}
+
+impl SmcTransport for SysfsSmc {
+    fn call(&self, input: &SMCKeyData, output: &mut SMCKeyData) -> Result<(), SmcError> {
+        if input.data8 == SMC_CMD_WRITE_BYTES {
+            return Err(SmcError::Transport(KERN_NOT_SUPPORTED));
+        }
+        *output = SMCKeyData::default();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        // other programs may select keys between the calls
+        state.selected = None;
+        self.exchange(&mut state, input, output)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::PermissionDenied => SmcError::PrivilegeViolation,
+                _ => SmcError::Transport(KERN_FAILURE),
+            })
+    }
+
+    /// `applesmc` only runs on Intel Macs.
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Big
+    }
+}
+
+/// Converts a key name or type to its four bytes, padded with spaces.
+fn four_cc(name: &str) -> [u8; 4] {
+    let mut code = *b"    ";
+    for (c, b) in code.iter_mut().zip(name.bytes()) {
+        *c = b;
+    }
+    code
+}
+
+#[test]
+fn fake_applesmc() -> io::Result<()> {
+    use crate::io::IOService;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Removes the fake tree, also when the test fails.
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A driver updating the `key_at_index_*` files of a directory when a
+    /// key is selected.
+    #[derive(Debug)]
+    struct FakeApplesmc {
+        dir: PathBuf,
+        keys: Vec<(&'static str, &'static str, &'static [u8])>,
+        selects: Arc<AtomicUsize>,
+    }
+
+    impl Device for FakeApplesmc {
+        fn read(&self, file: &str) -> io::Result<Vec<u8>> {
+            fs::read(self.dir.join(file))
+        }
+
+        fn write(&self, file: &str, contents: &str) -> io::Result<()> {
+            let index: usize = contents.parse().map_err(io::Error::other)?;
+            let (name, data_type, data) =
+                self.keys.get(index).ok_or(io::ErrorKind::InvalidInput)?;
+            self.selects.fetch_add(1, Ordering::Relaxed);
+            fs::write(self.dir.join(file), contents)?;
+            fs::write(self.dir.join("key_at_index_name"), format!("{}\n", name))?;
+            fs::write(
+                self.dir.join("key_at_index_type"),
+                format!("{}\n", data_type),
+            )?;
+            fs::write(self.dir.join("key_at_index_data"), data)
+        }
+    }
+
+    let dir = TempDir(std::env::temp_dir().join(format!("smc-lib-sysfs-{}", std::process::id())));
+    fs::create_dir_all(&dir.0)?;
+    let keys = vec![
+        ("AUPO", "ui8", &[0x00][..]),
+        ("F0Ac", "fpe2", &[0x12, 0xc0]),
+        ("TB0T", "sp78", &[0x19, 0x80]),
+        ("TC0P", "sp78", &[0x2d, 0x40]),
+        ("VP0R", "sp4b", &[0x60, 0x00]),
+    ];
+    fs::write(dir.0.join("key_count"), format!("{}\n", keys.len()))?;
+    let count = Arc::new(AtomicUsize::new(0));
+    let selects = || count.swap(0, Ordering::Relaxed);
+    let fake = Box::new(FakeApplesmc {
+        dir: dir.0.clone(),
+        keys,
+        selects: Arc::clone(&count),
+    });
+
+    let smc = IOService::with_transport(SysfsSmc::with_device(dir.0.clone(), fake)?);
+    assert_eq!(smc.keys_count(), Ok(5));
+    let val = smc.read_key(b"TB0T").map_err(io::Error::other)?;
+    assert_eq!(val.data_type, crate::data_type::DataType::from(*b"sp78"));
+    assert_eq!(val.valid_bytes(), [0x19, 0x80]);
+    let val = smc.read_key(b"F0Ac").map_err(io::Error::other)?;
+    assert_eq!(val.valid_bytes(), [0x12, 0xc0]);
+    let val = smc.read_key(b"VP0R").map_err(io::Error::other)?;
+    assert_eq!(val.valid_bytes(), [0x60, 0x00]);
+    assert_eq!(fs::read_to_string(dir.0.join("key_at_index"))?, "4");
+    assert_eq!(
+        smc.read_key(b"TA0P").map(|_| ()),
+        Err(SmcError::KeyNotFound)
+    );
+    // the names are cached, the key info and the bytes only select the key
+    selects();
+    assert!(smc.read_key(b"TC0P").is_ok());
+    assert_eq!(selects(), 2);
+    assert!(smc.read_key(b"TB0T").is_ok());
+    assert_eq!(selects(), 2);
+    let unsupported = Err(SmcError::Transport(KERN_NOT_SUPPORTED));
+    assert_eq!(smc.write_key(b"TB0T", &[0, 0]), unsupported);
+    let keys: Vec<_> = smc
+        .values_iter()
+        .map_err(io::Error::other)?
+        .filter_map(Result::ok)
+        .map(|v| v.key.to_string())
+        .collect();
+    assert_eq!(keys, ["AUPO", "F0Ac", "TB0T", "TC0P", "VP0R"]);
+
+    let root = dir.0.clone();
+    assert!(SysfsSmc::open_at(&root).is_ok());
+    drop(dir);
+    assert!(SysfsSmc::open_at(&root).is_err());
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use std::borrow::Cow;
 
 use crate::{
-    io::{ValError, err_str},
-    structs::SMCVal,
+    data_type::DataType,
+    io::ValError,
+    structs::{SMC_BYTES_LEN, SMCVal},
 };
 
 impl std::fmt::Display for SMCVal {
@@ -20,7 +21,7 @@ impl std::fmt::Display for SMCVal {
         for c in self.valid_bytes() {
             write!(f, " {:02x}", c)?;
         }
-        write!(f, ")")?;
+        write!(f, ") attrs: {}", self.attributes)?;
         if let Some(val) = self.data_value() {
             write!(f, " value: {}", val)?;
         }
@@ -31,20 +32,15 @@ impl std::fmt::Display for SMCVal {
 impl std::fmt::Display for ValError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(key) = self.key {
-            write!(f, "{} ", String::from_utf8_lossy(&key.to_be_bytes()))?;
+            write!(f, "{} ", key)?;
         }
         if let Some(data_type) = self.data_type {
-            write!(f, "{} ", String::from_utf8_lossy(&data_type.to_be_bytes()))?;
+            write!(f, "{} ", data_type)?;
         }
         if let Some(size) = self.data_size {
             write!(f, "size: {} ", size)?;
         }
-        write!(
-            f,
-            "index: {}, error: {}",
-            self.index,
-            err_str(self.err_code)
-        )?;
+        write!(f, "index: {}, error: {}", self.index, self.error)?;
         Ok(())
     }
 }
@@ -58,12 +54,14 @@ impl SMCVal {
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let val = smc.read_key(b"TB0T").unwrap();
     /// let bytes = val.valid_bytes();
     /// println!("Value bytes: {:02x?}", bytes);
+    /// # }
     /// ```
     pub fn valid_bytes(&self) -> &[u8] {
         let size = std::cmp::min(self.data_size as usize, self.bytes.len());
@@ -75,14 +73,16 @@ impl SMCVal {
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let val = smc.read_key(b"TB0T").unwrap();
     /// println!("Key name: {}", val.key_str());
+    /// # }
     /// ```
     pub fn key_str(&self) -> Cow<'_, str> {
-        String::from_utf8_lossy(&self.key)
+        String::from_utf8_lossy(self.key.as_bytes())
     }
 
     /// Returns the data type code as a string.
@@ -90,25 +90,28 @@ impl SMCVal {
     /// For type name that is shorter than 4 bytes, the string will include a tail space.
     /// This is designed intentionally, to keep the name length same.
     ///
-    /// See [AsahiLinux Docs](https://asahilinux.org/docs/hw/soc/smc) for common data types.
+    /// See [`DataType`] for the known data types.
     ///
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
     /// let val = smc.read_key(b"TB0T").unwrap();
     /// println!("Data type: {}", val.data_type_str());
+    /// # }
     /// ```
     pub fn data_type_str(&self) -> Cow<'_, str> {
-        String::from_utf8_lossy(&self.data_type)
+        Cow::Owned(self.data_type.to_string())
     }
 
     /// Parses the raw bytes into a typed value.
     ///
     /// This method attempts to interpret the raw byte data based on the
-    /// SMC data type code. Returns `None` if the data type is not recognized.
+    /// SMC data type code, in the byte order the value was read with, see
+    /// [`SMCVal::byte_order`]. Returns `None` if the data type is not recognized.
     ///
     /// Some data type is not supported, because it is unknown or not meaningful.
     ///
@@ -120,6 +123,7 @@ impl SMCVal {
     /// # Example
     ///
     /// ```no_run
+    /// # #[cfg(target_os = "macos")] {
     /// use smc_lib::io::IOService;
     ///
     /// let smc = IOService::init().unwrap();
@@ -130,11 +134,98 @@ impl SMCVal {
     /// } else {
     ///     println!("Not supported data type: {}", val.data_type_str());
     /// }
+    /// # }
     /// ```
     pub fn data_value(&self) -> Option<SmcValue> {
-        let type_code = SmcTypeCode::from_bytes(&self.data_type)?;
-        let val = parse_smc_value(type_code, &self.bytes);
-        Some(val)
+        self.data_value_in(self.byte_order)
+    }
+
+    /// Parses the raw bytes into a typed value, in the byte order `order`.
+    ///
+    /// Use this for a key whose byte order differs from the one it was read with.
+    pub fn data_value_in(&self, order: ByteOrder) -> Option<SmcValue> {
+        parse_smc_value(self.data_type, &self.bytes, self.data_size, order)
+    }
+
+    /// Diagnostic mode: parses the raw bytes in both byte orders.
+    ///
+    /// Returns the little endian and the big endian value, helpful to find
+    /// out the byte order of a key. Prefer [`data_value`](Self::data_value)
+    /// for anything else.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::data_type::DataType;
+    /// use smc_lib::structs::SMCVal;
+    /// use smc_lib::value::SmcValue;
+    ///
+    /// let mut val = SMCVal {
+    ///     data_type: DataType::Ui16,
+    ///     data_size: 2,
+    ///     ..Default::default()
+    /// };
+    /// val.bytes[..2].copy_from_slice(&[0x01, 0x02]);
+    /// assert_eq!(
+    ///     val.data_value_both(),
+    ///     Some((SmcValue::U16(0x0201), SmcValue::U16(0x0102)))
+    /// );
+    /// ```
+    pub fn data_value_both(&self) -> Option<(SmcValue, SmcValue)> {
+        let le = self.data_value_in(ByteOrder::Little)?;
+        let be = self.data_value_in(ByteOrder::Big)?;
+        Some((le, be))
+    }
+}
+
+/// Byte order of the numbers stored in a SMC.
+///
+/// Apple Silicon SMCs store numbers in little endian, Intel SMCs in big
+/// endian. The order is a property of the connection, see
+/// [`IOService::with_byte_order`](crate::io::IOService::with_byte_order).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Returns the byte order of the SMC for the target of the build, big
+    /// endian on `x86_64` (Intel Macs), little endian otherwise (Apple
+    /// Silicon).
+    ///
+    /// This is wrong for `x86_64` programs translated by Rosetta on Apple
+    /// Silicon, so the IOKit transport detects the byte order when it is
+    /// opened and only falls back to this.
+    pub const fn native() -> Self {
+        if cfg!(target_arch = "x86_64") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        }
+    }
+
+    /// Converts little endian bytes to this order, and back.
+    fn swap<const N: usize>(self, mut bytes: [u8; N]) -> [u8; N] {
+        if self == ByteOrder::Big {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    fn swap_to_vec<const N: usize>(self, bytes: [u8; N]) -> Vec<u8> {
+        self.swap(bytes).to_vec()
+    }
+}
+
+impl Default for ByteOrder {
+    fn default() -> Self {
+        Self::native()
     }
 }
 
@@ -143,29 +234,29 @@ impl SMCVal {
 /// SMC values can be various types including integers, floats, booleans,
 /// and strings. This enum provides type-safe access to the parsed data.
 ///
-/// `flt` is represented as both little endian and big endian number,
-/// because we are not sure.
-///
-/// According to [AsahiLinux Docs](https://asahilinux.org/docs/hw/soc/smc),
-/// > flt: a 32-bit single-precision IEEE float. In at least one case, the byte order is actually reversed.
+/// Numbers wider than one byte are stored in the byte order of the SMC, see
+/// [`ByteOrder`]. [`SMCVal::data_value_both`] decodes a value in both orders
+/// to diagnose keys with an unexpected order.
 ///
 /// # Example
 ///
 /// ```no_run
+/// # #[cfg(target_os = "macos")] {
 /// use smc_lib::io::IOService;
 /// use smc_lib::value::SmcValue;
 ///
 /// let smc = IOService::init().unwrap();
 /// let val = smc.read_key(b"TB0T").unwrap();
 ///
-/// if let Some(SmcValue::F32 { le, be }) = val.data_value() {
-///     println!("battery temperature: {}", le);
+/// if let Some(SmcValue::F32(temp)) = val.data_value() {
+///     println!("battery temperature: {}", temp);
 /// }
+/// # }
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub enum SmcValue {
-    /// Floating point value (both little and big endian interpretations)
-    F32 { le: f32, be: f32 },
+    /// Floating point value
+    F32(f32),
     /// Unsigned 8-bit integer
     U8(u8),
     /// Signed 8-bit integer
@@ -188,19 +279,85 @@ pub enum SmcValue {
     Chars(String),
     /// Fixed-point value (48.16 format)
     Ioft48_16(u64),
+    /// Unsigned fixed-point value of `fpXY`, the value is `raw / 2^fraction`
+    Fp { raw: u16, fraction: u8 },
+    /// Signed fixed-point value of `spXY`, the value is `raw / 2^fraction`
+    Sp { raw: i16, fraction: u8 },
+    /// PWM duty cycle of `pwm `, `0xffff` is 100%
+    Pwm(u16),
+    /// Fan descriptor of `{fds`
+    FanDescriptor(FanDescriptor),
+    /// Firmware revision of `{rev`
+    Revision(Revision),
+    /// Power limits of `{lim`
+    PowerLimits(PowerLimits),
+    /// Opaque bytes of `hex_`, and of structured types without a known layout
+    /// such as `{ala` and `{lso`
+    Hex(Vec<u8>),
+}
+
+/// Fan descriptor, the value of `{fds` keys such as `F0ID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanDescriptor {
+    /// Type of the fan
+    pub fan_type: u8,
+    /// Thermal zone cooled by the fan
+    pub zone: u8,
+    /// Location of the fan
+    pub location: u8,
+    /// Name of the fan, at most 12 bytes, e.g. `"Left Side"`
+    pub name: String,
+}
+
+/// Firmware revision, the value of `{rev` keys such as `REV `.
+///
+/// Displays like `2.16f68`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    pub major: u8,
+    pub minor: u8,
+    pub build: u8,
+    pub release: u16,
+}
+
+/// Power limits, the value of `{lim` keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerLimits {
+    pub cpu: u8,
+    pub gpu: u8,
+    pub mem: u8,
+}
+
+impl std::fmt::Display for FanDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (type: {}, zone: {}, location: {})",
+            self.name, self.fan_type, self.zone, self.location
+        )
+    }
+}
+
+impl std::fmt::Display for Revision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:x}.{:x}{:x}{}",
+            self.major, self.minor, self.build, self.release
+        )
+    }
+}
+
+impl std::fmt::Display for PowerLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cpu: {}, gpu: {}, mem: {}", self.cpu, self.gpu, self.mem)
+    }
 }
 
 impl std::fmt::Display for SmcValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SmcValue::F32 { le, be } => {
-                if le.to_bits() == be.to_bits() {
-                    write!(f, "{}", le)
-                } else {
-                    // Helpful when endianness is unclear.
-                    write!(f, "le={}, be={}", le, be)
-                }
-            }
+            SmcValue::F32(v) => write!(f, "{}", v),
 
             SmcValue::U8(v) => write!(f, "{}", v),
             SmcValue::I8(v) => write!(f, "{}", v),
@@ -218,26 +375,374 @@ impl std::fmt::Display for SmcValue {
                 let decoded = ((raw >> 16) as f64) + ((raw & 0xFFFF) as f64 / 65536.0);
                 write!(f, "{}", decoded)
             }
+
+            SmcValue::Fp { raw, fraction } => match self.as_f64() {
+                Some(v) => write!(f, "{}", v),
+                None => write!(f, "{} / 2^{}", raw, fraction),
+            },
+            SmcValue::Sp { raw, fraction } => match self.as_f64() {
+                Some(v) => write!(f, "{}", v),
+                None => write!(f, "{} / 2^{}", raw, fraction),
+            },
+
+            SmcValue::Pwm(v) => write!(f, "{:.1}%", f64::from(*v) * 100.0 / 65535.0),
+            SmcValue::FanDescriptor(v) => write!(f, "{}", v),
+            SmcValue::Revision(v) => write!(f, "{}", v),
+            SmcValue::PowerLimits(v) => write!(f, "{}", v),
+
+            SmcValue::Hex(bytes) => {
+                write!(f, "0x")?;
+                for b in bytes {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SmcValue {
+    /// Returns the value of the integer variants.
+    fn as_integer(&self) -> Option<i128> {
+        let n = match *self {
+            SmcValue::U8(v) => v.into(),
+            SmcValue::I8(v) => v.into(),
+            SmcValue::I16(v) => v.into(),
+            SmcValue::U16(v) => v.into(),
+            SmcValue::U32(v) => v.into(),
+            SmcValue::I32(v) => v.into(),
+            SmcValue::I64(v) => v.into(),
+            SmcValue::U64(v) => v.into(),
+            _ => return None,
+        };
+        Some(n)
+    }
+
+    /// Returns the value as a number.
+    ///
+    /// `Pwm` returns the duty cycle
+    /// in percent. Returns `None` for `Bool`, `Chars` and the structured values,
+    /// and for `Fp` and `Sp` values with more `fraction` bits than `raw` has.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::value::SmcValue;
+    ///
+    /// let val = SmcValue::Sp { raw: 0x1980, fraction: 8 };
+    /// assert_eq!(val.as_f64(), Some(25.5));
+    /// assert_eq!(val.to_string(), "25.5");
+    /// ```
+    pub fn as_f64(&self) -> Option<f64> {
+        let n = match *self {
+            SmcValue::F32(v) => v.into(),
+            SmcValue::Ioft48_16(raw) => raw as f64 / 65536.0,
+            SmcValue::Fp { raw, fraction } => f64::from(raw) / fixed_point_scale(fraction, 16)?,
+            SmcValue::Sp { raw, fraction } => f64::from(raw) / fixed_point_scale(fraction, 15)?,
+            SmcValue::Pwm(v) => f64::from(v) * 100.0 / 65535.0,
+            _ => self.as_integer()? as f64,
+        };
+        Some(n)
+    }
+
+    /// Encodes the value into the bytes of a key of `data_type`.
+    ///
+    /// This is the inverse of [`SMCVal::data_value`]. Integers can be
+    /// encoded as any integer type they fit in, and as `flt`. Any number can
+    /// be encoded as `fpXY` and `spXY`, rounded to the nearest step. Numbers
+    /// wider than one byte are written in `order`.
+    ///
+    /// # Errors
+    ///
+    /// - [`EncodeError::TypeMismatch`] - The value can not be stored as `data_type`
+    /// - [`EncodeError::OutOfRange`] - The value does not fit in `data_type`
+    /// - [`EncodeError::TooLong`] - The string is longer than [`SMC_BYTES_LEN`]
+    /// - [`EncodeError::UnsupportedType`] - `data_type` can not be encoded
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::data_type::DataType;
+    /// use smc_lib::value::{ByteOrder, EncodeError, SmcValue};
+    ///
+    /// let val = SmcValue::from(300u32);
+    /// assert_eq!(val.encode(DataType::Ui16, ByteOrder::Little), Ok(vec![0x2c, 0x01]));
+    /// assert_eq!(val.encode(DataType::Ui16, ByteOrder::Big), Ok(vec![0x01, 0x2c]));
+    /// assert_eq!(
+    ///     val.encode(DataType::Ui8, ByteOrder::Little),
+    ///     Err(EncodeError::OutOfRange(DataType::Ui8))
+    /// );
+    /// ```
+    pub fn encode(&self, data_type: DataType, order: ByteOrder) -> Result<Vec<u8>, EncodeError> {
+        let integer = || {
+            self.as_integer()
+                .ok_or(EncodeError::TypeMismatch(data_type))
+        };
+        let out_of_range = |_| EncodeError::OutOfRange(data_type);
+        let bytes = match data_type {
+            DataType::Ui8 => order.swap_to_vec(
+                u8::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Si8 => order.swap_to_vec(
+                i8::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Ui16 => order.swap_to_vec(
+                u16::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Si16 => order.swap_to_vec(
+                i16::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Ui32 => order.swap_to_vec(
+                u32::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Si32 => order.swap_to_vec(
+                i32::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Ui64 => order.swap_to_vec(
+                u64::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+            DataType::Si64 => order.swap_to_vec(
+                i64::try_from(integer()?)
+                    .map_err(out_of_range)?
+                    .to_le_bytes(),
+            ),
+
+            DataType::Flt => {
+                let v = match *self {
+                    SmcValue::F32(v) => v,
+                    _ => integer()? as f32,
+                };
+                order.swap_to_vec(v.to_le_bytes())
+            }
+
+            DataType::Flag => match *self {
+                SmcValue::Bool(v) => vec![v as u8],
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Ch8 => match self {
+                SmcValue::Chars(s) if s.len() > SMC_BYTES_LEN => {
+                    return Err(EncodeError::TooLong(s.len()));
+                }
+                SmcValue::Chars(s) => s.as_bytes().to_vec(),
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Ioft => {
+                let raw = match *self {
+                    SmcValue::Ioft48_16(raw) => raw,
+                    _ => {
+                        let n = u64::try_from(integer()?).map_err(out_of_range)?;
+                        if n >> 48 != 0 {
+                            return Err(EncodeError::OutOfRange(data_type));
+                        }
+                        n << 16
+                    }
+                };
+                order.swap_to_vec(raw.to_le_bytes())
+            }
+
+            DataType::Fp(bits) => {
+                let raw = self.fixed_point_raw(data_type, bits.fraction())?;
+                let raw = u16::try_from(raw).map_err(out_of_range)?;
+                order.swap_to_vec(raw.to_le_bytes())
+            }
+
+            DataType::Sp(bits) => {
+                let raw = self.fixed_point_raw(data_type, bits.fraction())?;
+                let raw = i16::try_from(raw).map_err(out_of_range)?;
+                order.swap_to_vec(raw.to_le_bytes())
+            }
+
+            DataType::Pwm => match *self {
+                SmcValue::Pwm(v) => order.swap_to_vec(v.to_le_bytes()),
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Fds => match self {
+                SmcValue::FanDescriptor(fds) if fds.name.len() > FDS_NAME_LEN => {
+                    return Err(EncodeError::OutOfRange(data_type));
+                }
+                SmcValue::FanDescriptor(fds) => {
+                    let mut bytes = vec![fds.fan_type, fds.zone, fds.location, 0];
+                    bytes.extend_from_slice(fds.name.as_bytes());
+                    bytes.resize(4 + FDS_NAME_LEN, 0);
+                    bytes
+                }
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Rev => match *self {
+                SmcValue::Revision(rev) => {
+                    let [r0, r1] = order.swap(rev.release.to_le_bytes());
+                    vec![rev.major, rev.minor, rev.build, 0, r0, r1]
+                }
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Lim => match *self {
+                SmcValue::PowerLimits(lim) => vec![lim.cpu, lim.gpu, lim.mem],
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            DataType::Hex | DataType::Ala | DataType::Lso => match self {
+                SmcValue::Hex(bytes) if bytes.len() > SMC_BYTES_LEN => {
+                    return Err(EncodeError::TooLong(bytes.len()));
+                }
+                SmcValue::Hex(bytes) => bytes.clone(),
+                _ => return Err(EncodeError::TypeMismatch(data_type)),
+            },
+
+            _ => return Err(EncodeError::UnsupportedType(data_type)),
+        };
+        Ok(bytes)
+    }
+
+    /// Returns `self * 2^fraction` rounded to the nearest integer, for a
+    /// fixed-point type, whose [`FixedPoint`](crate::data_type::FixedPoint)
+    /// has at most 15 fraction bits.
+    fn fixed_point_raw(&self, data_type: DataType, fraction: u8) -> Result<i64, EncodeError> {
+        let v = self.as_f64().ok_or(EncodeError::TypeMismatch(data_type))?;
+        let scale = f64::from(1u32 << fraction);
+        let raw = (v * scale).round();
+        if !(i64::MIN as f64..=i64::MAX as f64).contains(&raw) {
+            return Err(EncodeError::OutOfRange(data_type));
         }
+        Ok(raw as i64)
     }
 }
 
+/// Returns `2^fraction`, `None` if a fixed-point value of `bits` bits can not
+/// have `fraction` bits.
+fn fixed_point_scale(fraction: u8, bits: u8) -> Option<f64> {
+    (fraction <= bits).then(|| f64::from(1u32 << fraction))
+}
+
+impl From<f32> for SmcValue {
+    fn from(v: f32) -> Self {
+        SmcValue::F32(v)
+    }
+}
+
+impl From<u8> for SmcValue {
+    fn from(v: u8) -> Self {
+        SmcValue::U8(v)
+    }
+}
+
+impl From<i8> for SmcValue {
+    fn from(v: i8) -> Self {
+        SmcValue::I8(v)
+    }
+}
+
+impl From<i16> for SmcValue {
+    fn from(v: i16) -> Self {
+        SmcValue::I16(v)
+    }
+}
+
+impl From<u16> for SmcValue {
+    fn from(v: u16) -> Self {
+        SmcValue::U16(v)
+    }
+}
+
+impl From<u32> for SmcValue {
+    fn from(v: u32) -> Self {
+        SmcValue::U32(v)
+    }
+}
+
+impl From<i32> for SmcValue {
+    fn from(v: i32) -> Self {
+        SmcValue::I32(v)
+    }
+}
+
+impl From<i64> for SmcValue {
+    fn from(v: i64) -> Self {
+        SmcValue::I64(v)
+    }
+}
+
+impl From<u64> for SmcValue {
+    fn from(v: u64) -> Self {
+        SmcValue::U64(v)
+    }
+}
+
+impl From<bool> for SmcValue {
+    fn from(v: bool) -> Self {
+        SmcValue::Bool(v)
+    }
+}
+
+impl From<&str> for SmcValue {
+    fn from(v: &str) -> Self {
+        SmcValue::Chars(v.to_owned())
+    }
+}
+
+impl From<String> for SmcValue {
+    fn from(v: String) -> Self {
+        SmcValue::Chars(v)
+    }
+}
+
+/// Error returned when a [`SmcValue`] can not be encoded, see [`SmcValue::encode`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SmcTypeCode {
-    Flt,
-    Ui8,
-    Si8,
-    Si16,
-    Ui16,
-    Ui32,
-    Si32,
-    Si64,
-    Ui64,
-    Chars,
-    Flag,
-    Ioft,
+pub enum EncodeError {
+    /// The data type can not be encoded.
+    UnsupportedType(DataType),
+    /// The value can not be stored as the data type.
+    TypeMismatch(DataType),
+    /// The value does not fit in the data type.
+    OutOfRange(DataType),
+    /// The encoded value is longer than [`SMC_BYTES_LEN`], contains the length in bytes.
+    TooLong(usize),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::UnsupportedType(data_type) => {
+                write!(f, "can not encode data type `{}`", data_type)
+            }
+            EncodeError::TypeMismatch(data_type) => {
+                write!(f, "value can not be stored as `{}`", data_type)
+            }
+            EncodeError::OutOfRange(data_type) => {
+                write!(f, "value is out of the range of `{}`", data_type)
+            }
+            EncodeError::TooLong(len) => write!(
+                f,
+                "value is too long, {} bytes, at most {}",
+                len, SMC_BYTES_LEN
+            ),
+        }
+    }
 }
 
+impl std::error::Error for EncodeError {}
+
+/// Length of the name in a `{fds` fan descriptor.
+const FDS_NAME_LEN: usize = 12;
+
 trait TakeN {
     /// # Panic
     /// May panic if N is out of bounds.
@@ -252,85 +757,64 @@ impl TakeN for [u8] {
     }
 }
 
-impl SmcTypeCode {
-    fn from_bytes(code: &[u8; 4]) -> Option<Self> {
-        let code = match code {
-            b"flt " => Self::Flt,
-            b"ui8 " => Self::Ui8,
-            b"si8 " => Self::Si8,
-            b"si16" => Self::Si16,
-            b"ui16" => Self::Ui16,
-            b"ui32" => Self::Ui32,
-            b"si32" => Self::Si32,
-            b"si64" => Self::Si64,
-            b"ui64" => Self::Ui64,
-            b"ch8*" => Self::Chars,
-            b"flag" => Self::Flag,
-            b"ioft" => Self::Ioft,
-            _ => return None,
-        };
-        Some(code)
-    }
-}
-
-fn parse_smc_value(type_code: SmcTypeCode, data: &[u8; 32]) -> SmcValue {
-    match type_code {
-        SmcTypeCode::Flt => {
-            let b = data.take::<4>();
-            let be = u32::from_be_bytes(b);
-            let le = u32::from_le_bytes(b);
-            SmcValue::F32 {
-                le: f32::from_bits(le),
-                be: f32::from_bits(be),
-            }
+fn parse_smc_value(
+    data_type: DataType,
+    data: &[u8; 32],
+    size: u32,
+    order: ByteOrder,
+) -> Option<SmcValue> {
+    let val = match data_type {
+        DataType::Flt => {
+            let b = order.swap(data.take::<4>());
+            SmcValue::F32(f32::from_le_bytes(b))
         }
 
-        SmcTypeCode::Ui8 => SmcValue::U8(data[0]),
-        SmcTypeCode::Si8 => SmcValue::I8(data[0] as i8),
+        DataType::Ui8 => SmcValue::U8(data[0]),
+        DataType::Si8 => SmcValue::I8(data[0] as i8),
 
-        SmcTypeCode::Si16 => {
-            let b = data.take::<2>();
+        DataType::Si16 => {
+            let b = order.swap(data.take::<2>());
             let n = i16::from_le_bytes(b);
             SmcValue::I16(n)
         }
 
-        SmcTypeCode::Ui16 => {
-            let b = data.take::<2>();
+        DataType::Ui16 => {
+            let b = order.swap(data.take::<2>());
             let n = u16::from_le_bytes(b);
             SmcValue::U16(n)
         }
 
-        SmcTypeCode::Ui32 => {
-            let b = data.take::<4>();
+        DataType::Ui32 => {
+            let b = order.swap(data.take::<4>());
             let n = u32::from_le_bytes(b);
             SmcValue::U32(n)
         }
 
-        SmcTypeCode::Si32 => {
-            let b = data.take::<4>();
+        DataType::Si32 => {
+            let b = order.swap(data.take::<4>());
             let n = i32::from_le_bytes(b);
             SmcValue::I32(n)
         }
 
-        SmcTypeCode::Si64 => {
-            let b = data.take::<8>();
+        DataType::Si64 => {
+            let b = order.swap(data.take::<8>());
             let n = i64::from_le_bytes(b);
             SmcValue::I64(n)
         }
 
-        SmcTypeCode::Ui64 => {
-            let b = data.take::<8>();
+        DataType::Ui64 => {
+            let b = order.swap(data.take::<8>());
             let n = u64::from_le_bytes(b);
 
             SmcValue::U64(n)
         }
 
-        SmcTypeCode::Flag => {
+        DataType::Flag => {
             let x = data[0];
             SmcValue::Bool(x != 0)
         }
 
-        SmcTypeCode::Chars => {
+        DataType::Ch8 => {
             // Treat as ASCII; trim at first NUL if present.
             let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
             let slice = &data[..end];
@@ -338,10 +822,209 @@ fn parse_smc_value(type_code: SmcTypeCode, data: &[u8; 32]) -> SmcValue {
             SmcValue::Chars(s)
         }
 
-        SmcTypeCode::Ioft => {
-            let b = data.take::<8>();
+        DataType::Ioft => {
+            let b = order.swap(data.take::<8>());
             let n = u64::from_le_bytes(b);
             SmcValue::Ioft48_16(n)
         }
+
+        DataType::Fp(bits) => {
+            let raw = u16::from_le_bytes(order.swap(data.take::<2>()));
+            SmcValue::Fp {
+                raw,
+                fraction: bits.fraction(),
+            }
+        }
+
+        DataType::Sp(bits) => {
+            let raw = i16::from_le_bytes(order.swap(data.take::<2>()));
+            SmcValue::Sp {
+                raw,
+                fraction: bits.fraction(),
+            }
+        }
+
+        DataType::Pwm => SmcValue::Pwm(u16::from_le_bytes(order.swap(data.take::<2>()))),
+
+        DataType::Fds => {
+            let name = &data[4..4 + FDS_NAME_LEN];
+            let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            SmcValue::FanDescriptor(FanDescriptor {
+                fan_type: data[0],
+                zone: data[1],
+                location: data[2],
+                name: String::from_utf8_lossy(&name[..end]).trim_end().to_owned(),
+            })
+        }
+
+        DataType::Rev => SmcValue::Revision(Revision {
+            major: data[0],
+            minor: data[1],
+            build: data[2],
+            release: u16::from_le_bytes(order.swap([data[4], data[5]])),
+        }),
+
+        DataType::Lim => SmcValue::PowerLimits(PowerLimits {
+            cpu: data[0],
+            gpu: data[1],
+            mem: data[2],
+        }),
+
+        // the layout of `{ala` and `{lso` is not known
+        DataType::Hex | DataType::Ala | DataType::Lso => {
+            let size = std::cmp::min(size as usize, data.len());
+            SmcValue::Hex(data[..size].to_vec())
+        }
+
+        // not decoded
+        _ => return None,
+    };
+    Some(val)
+}
+
+#[test]
+fn encode_round_trip() {
+    let values = [
+        (DataType::Ui8, SmcValue::U8(0xfe)),
+        (DataType::Si8, SmcValue::I8(-2)),
+        (DataType::Ui16, SmcValue::U16(0x1234)),
+        (DataType::Si16, SmcValue::I16(-300)),
+        (DataType::Ui32, SmcValue::U32(0x1234_5678)),
+        (DataType::Si32, SmcValue::I32(-70000)),
+        (DataType::Ui64, SmcValue::U64(u64::MAX)),
+        (DataType::Si64, SmcValue::I64(i64::MIN)),
+        (DataType::Flag, SmcValue::Bool(true)),
+        (DataType::Ch8, SmcValue::Chars("j314s".to_owned())),
+        (DataType::Ioft, SmcValue::Ioft48_16(0x0003_8000)),
+        (DataType::Flt, SmcValue::from(25.8f32)),
+    ];
+    for (data_type, value) in values {
+        for order in [ByteOrder::Little, ByteOrder::Big] {
+            let bytes = value.encode(data_type, order).expect("value fits");
+            let mut data = [0; SMC_BYTES_LEN];
+            data[..bytes.len()].copy_from_slice(&bytes);
+            let decoded = parse_smc_value(data_type, &data, bytes.len() as u32, order);
+            assert_eq!(decoded.as_ref(), Some(&value));
+        }
     }
+    assert_eq!(
+        SmcValue::from(25.8f32).encode(DataType::Flt, ByteOrder::Big),
+        Ok(25.8f32.to_be_bytes().to_vec())
+    );
+    assert_eq!(
+        SmcValue::from(-1i8).encode(DataType::Ui32, ByteOrder::Little),
+        Err(EncodeError::OutOfRange(DataType::Ui32))
+    );
+    assert_eq!(
+        SmcValue::from(3u8).encode(DataType::Ioft, ByteOrder::Little),
+        Ok(vec![0, 0, 3, 0, 0, 0, 0, 0])
+    );
+    assert_eq!(
+        SmcValue::from(3u8).encode(DataType::Flt, ByteOrder::Little),
+        Ok(3f32.to_le_bytes().to_vec())
+    );
+    assert_eq!(
+        SmcValue::from(1u8).encode(DataType::Flag, ByteOrder::Little),
+        Err(EncodeError::TypeMismatch(DataType::Flag))
+    );
+    assert_eq!(
+        SmcValue::from("a".repeat(33)).encode(DataType::Ch8, ByteOrder::Little),
+        Err(EncodeError::TooLong(33))
+    );
+    let abcd = DataType::from(*b"abcd");
+    assert_eq!(
+        SmcValue::from(1u8).encode(abcd, ByteOrder::Little),
+        Err(EncodeError::UnsupportedType(abcd))
+    );
+}
+
+#[test]
+fn fixed_point() {
+    let sp78 = DataType::from(*b"sp78");
+    let fpe2 = DataType::from(*b"fpe2");
+    let mut data = [0; SMC_BYTES_LEN];
+    data[..2].copy_from_slice(&[0x19, 0x80]);
+    assert_eq!(
+        parse_smc_value(sp78, &data, 2, ByteOrder::Big),
+        Some(SmcValue::Sp {
+            raw: 0x1980,
+            fraction: 8
+        })
+    );
+    // 1200 rpm
+    data[..2].copy_from_slice(&[0x12, 0xc0]);
+    let val = parse_smc_value(fpe2, &data, 2, ByteOrder::Big).expect("fpe2 is decoded");
+    assert_eq!(val.to_string(), "1200");
+    assert_eq!(val.encode(fpe2, ByteOrder::Big), Ok(vec![0x12, 0xc0]));
+
+    assert_eq!(
+        SmcValue::from(-1.5f32).encode(sp78, ByteOrder::Big),
+        Ok(vec![0xfe, 0x80])
+    );
+    assert_eq!(
+        SmcValue::from(25.5f32).encode(sp78, ByteOrder::Big),
+        Ok(vec![0x19, 0x80])
+    );
+    assert_eq!(
+        SmcValue::from(128u8).encode(sp78, ByteOrder::Big),
+        Err(EncodeError::OutOfRange(sp78))
+    );
+    assert_eq!(
+        SmcValue::from(-1i8).encode(fpe2, ByteOrder::Big),
+        Err(EncodeError::OutOfRange(fpe2))
+    );
+    let fp1f = DataType::from(*b"fp1f");
+    assert_eq!(
+        SmcValue::from(1.5f32).encode(fp1f, ByteOrder::Big),
+        Ok(vec![0xc0, 0x00])
+    );
+    assert_eq!(
+        SmcValue::from(2u8).encode(fp1f, ByteOrder::Big),
+        Err(EncodeError::OutOfRange(fp1f))
+    );
+
+    let invalid = SmcValue::Sp {
+        raw: 1,
+        fraction: 40,
+    };
+    assert_eq!(invalid.as_f64(), None);
+    assert_eq!(invalid.to_string(), "1 / 2^40");
+    let fp0g = SmcValue::Fp {
+        raw: 0x8000,
+        fraction: 16,
+    };
+    assert_eq!(fp0g.as_f64(), Some(0.5));
+}
+
+#[test]
+fn structured_types() {
+    let decode = |data_type: &[u8; 4], bytes: &[u8]| {
+        let mut data = [0; SMC_BYTES_LEN];
+        data[..bytes.len()].copy_from_slice(bytes);
+        let data_type = DataType::from(*data_type);
+        let val = parse_smc_value(data_type, &data, bytes.len() as u32, ByteOrder::Big)
+            .expect("type is decoded");
+        assert_eq!(val.encode(data_type, ByteOrder::Big).as_deref(), Ok(bytes));
+        val
+    };
+    let val = decode(b"{fds", b"\0\0\x03\0Left Side\0\0\0");
+    assert_eq!(
+        val,
+        SmcValue::FanDescriptor(FanDescriptor {
+            fan_type: 0,
+            zone: 0,
+            location: 3,
+            name: "Left Side".to_owned(),
+        })
+    );
+    let val = decode(b"{rev", &[0x02, 0x16, 0x0f, 0x00, 0x00, 0x44]);
+    assert_eq!(val.to_string(), "2.16f68");
+    let val = decode(b"{lim", &[0x00, 0x01, 0x02]);
+    assert_eq!(val.to_string(), "cpu: 0, gpu: 1, mem: 2");
+    let val = decode(b"pwm ", &[0x80, 0x00]);
+    assert_eq!(val.to_string(), "50.0%");
+    let val = decode(b"hex_", &[0xde, 0xad, 0xbe]);
+    assert_eq!(val.to_string(), "0xdeadbe");
+    let val = decode(b"{ala", &[0x01, 0x02]);
+    assert_eq!(val, SmcValue::Hex(vec![0x01, 0x02]));
 }
@@ -0,0 +1,493 @@
+//! Polling of SMC keys, reporting the changes of their values.
+//!
+//! A [`Watcher`] reads each watched key at its own interval and emits a
+//! [`WatchEvent`] when the value changes. Numeric keys can have a deadband,
+//! so that sensor noise does not produce an event at every read.
+//!
+//! The time is taken from a [`Clock`], [`ManualClock`] lets tests drive the
+//! watcher without waiting.
+//!
+//! # Example
+//!
+//! ```
+//! use smc_lib::io::IOService;
+//! use smc_lib::mock::{MockKey, MockSmc};
+//! use smc_lib::structs::SMC_ATTR_READ;
+//! use smc_lib::watch::{ManualClock, WatchEvent, Watcher};
+//! use std::time::Duration;
+//!
+//! let smc = IOService::with_transport(MockSmc::sample());
+//! let clock = ManualClock::new();
+//! let mut watcher = Watcher::new(&smc)
+//!     .with_clock(&clock)
+//!     .watch_with_deadband(b"TB0T", Duration::from_secs(1), 0.5);
+//! // the first read only records the values
+//! assert!(watcher.poll().is_empty());
+//!
+//! let temp = 27.0f32.to_le_bytes();
+//! smc.transport().insert(*b"TB0T", MockKey::new(b"flt ", SMC_ATTR_READ, &temp));
+//! clock.advance(Duration::from_secs(1));
+//! match &watcher.poll()[..] {
+//!     [WatchEvent::Changed { key, old, new, .. }] => {
+//!         println!("{}: {:?} -> {:?}", key, old.data_value(), new.data_value());
+//!     }
+//!     events => panic!("unexpected events {:?}", events),
+//! }
+//! ```
+
+use crate::io::{IOService, SmcError, SmcTransport};
+use crate::key::SmcKey;
+use crate::structs::SMCVal;
+use std::{
+    ops::{ControlFlow, Deref},
+    sync::{Arc, Mutex, PoisonError, Weak, mpsc},
+    time::{Duration, Instant},
+};
+
+/// Source of time of a [`Watcher`].
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Blocks until `deadline`, returns at once if it has passed.
+    fn sleep_until(&self, deadline: Instant);
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        (**self).sleep_until(deadline)
+    }
+}
+
+/// The real time, [`Instant::now`] and [`std::thread::sleep`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// A clock that only moves when told to, for tests.
+///
+/// Sleeping moves the clock to the deadline at once.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now = (*now).max(deadline);
+    }
+}
+
+/// Event emitted by a [`Watcher`].
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The value of `key` changed from `old` to `new`, read at `at`.
+    ///
+    /// `old` is the value of the previous event of the key, or its first
+    /// read, so changes smaller than the deadband add up.
+    Changed {
+        key: SmcKey,
+        old: SMCVal,
+        new: SMCVal,
+        at: Instant,
+    },
+    /// `key` could not be read at `at`, it is read again after its interval.
+    Failed {
+        key: SmcKey,
+        error: SmcError,
+        at: Instant,
+    },
+}
+
+/// Creates a channel for [`Watcher::run_into`].
+///
+/// Unlike a plain [`mpsc`] channel, the sender can tell that the receiver
+/// was dropped without sending an event.
+pub fn channel() -> (EventSender, EventReceiver) {
+    let (tx, rx) = mpsc::channel();
+    let alive = Arc::new(());
+    let sender = EventSender {
+        tx,
+        alive: Arc::downgrade(&alive),
+    };
+    (sender, EventReceiver { rx, _alive: alive })
+}
+
+/// Sending half of a [`channel`].
+#[derive(Clone, Debug)]
+pub struct EventSender {
+    tx: mpsc::Sender<WatchEvent>,
+    alive: Weak<()>,
+}
+
+impl EventSender {
+    /// Sends an event, fails if the receiver was dropped.
+    pub fn send(&self, event: WatchEvent) -> Result<(), mpsc::SendError<WatchEvent>> {
+        self.tx.send(event)
+    }
+
+    /// Returns whether the receiver was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.alive.strong_count() == 0
+    }
+}
+
+/// Receiving half of a [`channel`], it dereferences to a [`mpsc::Receiver`]
+/// and iterates over the events until the senders are dropped.
+#[derive(Debug)]
+pub struct EventReceiver {
+    rx: mpsc::Receiver<WatchEvent>,
+    _alive: Arc<()>,
+}
+
+impl Deref for EventReceiver {
+    type Target = mpsc::Receiver<WatchEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl Iterator for EventReceiver {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<WatchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+/// A watched key.
+struct Watch {
+    key: SmcKey,
+    interval: Duration,
+    deadband: Option<f64>,
+    /// `None` until the first read
+    due: Option<Instant>,
+    last: Option<SMCVal>,
+}
+
+impl Watch {
+    /// Records `new`, returns the previous value if the change is reported.
+    fn update(&mut self, new: SMCVal) -> Option<SMCVal> {
+        let Some(old) = self.last else {
+            self.last = Some(new);
+            return None;
+        };
+        if old.data_type == new.data_type && old.valid_bytes() == new.valid_bytes() {
+            return None;
+        }
+        let numbers = old
+            .data_value()
+            .and_then(|v| v.as_f64())
+            .zip(new.data_value().and_then(|v| v.as_f64()));
+        if let (Some(deadband), Some((old, new))) = (self.deadband, numbers)
+            && (new - old).abs() < deadband
+        {
+            return None;
+        }
+        self.last = Some(new);
+        Some(old)
+    }
+}
+
+/// Polls SMC keys and reports their changes.
+///
+/// The first read of a key records its value without an event. After that,
+/// a [`WatchEvent::Changed`] is emitted when the bytes of the value change,
+/// or for keys with a deadband and a numeric value (see
+/// [`SmcValue::as_f64`](crate::value::SmcValue::as_f64)), when the value
+/// moves by at least the deadband.
+///
+/// Use [`poll`](Self::poll) from an existing loop, or [`run`](Self::run)
+/// and [`run_into`](Self::run_into) to let the watcher sleep between reads.
+pub struct Watcher<'a, T, C = SystemClock> {
+    smc: &'a IOService<T>,
+    clock: C,
+    watches: Vec<Watch>,
+}
+
+impl<'a, T: SmcTransport> Watcher<'a, T> {
+    /// Creates a watcher without keys, using the real time.
+    pub fn new(smc: &'a IOService<T>) -> Self {
+        Self {
+            smc,
+            clock: SystemClock,
+            watches: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T: SmcTransport, C: Clock> Watcher<'a, T, C> {
+    /// Replaces the clock, e.g. with a [`ManualClock`] in tests.
+    pub fn with_clock<D: Clock>(self, clock: D) -> Watcher<'a, T, D> {
+        Watcher {
+            smc: self.smc,
+            clock,
+            watches: self.watches,
+        }
+    }
+
+    /// Watches `key`, reading it every `interval`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn watch(mut self, key: impl Into<SmcKey>, interval: Duration) -> Self {
+        self.add(key.into(), interval, None);
+        self
+    }
+
+    /// Watches `key`, reading it every `interval`, and reports numeric
+    /// values only when they move by at least `deadband`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn watch_with_deadband(
+        mut self,
+        key: impl Into<SmcKey>,
+        interval: Duration,
+        deadband: f64,
+    ) -> Self {
+        self.add(key.into(), interval, Some(deadband));
+        self
+    }
+
+    fn add(&mut self, key: SmcKey, interval: Duration, deadband: Option<f64>) {
+        assert!(!interval.is_zero(), "watch interval must not be zero");
+        self.watches.push(Watch {
+            key,
+            interval,
+            deadband,
+            due: None,
+            last: None,
+        });
+    }
+
+    /// Reads the keys whose interval has elapsed and returns the events.
+    ///
+    /// Does not sleep, keys that are not due are skipped.
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        let now = self.clock.now();
+        let mut events = Vec::new();
+        for watch in &mut self.watches {
+            if watch.due.is_some_and(|due| due > now) {
+                continue;
+            }
+            // keep the reads on the interval grid, unless a read was missed
+            let next = watch.due.map_or(now, |due| due + watch.interval);
+            watch.due = Some(if next > now {
+                next
+            } else {
+                now + watch.interval
+            });
+            match self.smc.read_key(watch.key) {
+                Ok(new) => {
+                    if let Some(old) = watch.update(new) {
+                        events.push(WatchEvent::Changed {
+                            key: watch.key,
+                            old,
+                            new,
+                            at: now,
+                        });
+                    }
+                }
+                Err(error) => events.push(WatchEvent::Failed {
+                    key: watch.key,
+                    error,
+                    at: now,
+                }),
+            }
+        }
+        events
+    }
+
+    /// Returns when the next key is due, `None` if no key is watched.
+    pub fn next_due(&self) -> Option<Instant> {
+        let now = self.clock.now();
+        self.watches.iter().map(|w| w.due.unwrap_or(now)).min()
+    }
+
+    /// Polls the keys until `f` returns [`ControlFlow::Break`], sleeping
+    /// until the next key is due.
+    ///
+    /// Returns at once if no key is watched.
+    pub fn run(&mut self, f: impl FnMut(WatchEvent) -> ControlFlow<()>) {
+        self.run_while(|| true, f)
+    }
+
+    /// Polls the keys and sends the events to `events`, until the receiver
+    /// is dropped.
+    ///
+    /// The receiver is checked each time the watcher wakes up, so the
+    /// watcher returns at the latest after the shortest interval.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smc_lib::io::IOService;
+    /// use smc_lib::mock::MockSmc;
+    /// use smc_lib::watch::{self, Watcher};
+    /// use std::time::Duration;
+    ///
+    /// let smc = IOService::with_transport(MockSmc::sample());
+    /// let (tx, rx) = watch::channel();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let mut watcher = Watcher::new(&smc).watch(b"TB0T", Duration::from_millis(10));
+    ///         watcher.run_into(tx);
+    ///     });
+    ///     // stops the watcher
+    ///     drop(rx);
+    /// });
+    /// ```
+    pub fn run_into(&mut self, events: EventSender) {
+        self.run_while(
+            || !events.is_closed(),
+            |event| match events.send(event) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            },
+        )
+    }
+
+    /// Runs until `alive` returns `false` after a sleep, or `f` breaks.
+    fn run_while(
+        &mut self,
+        mut alive: impl FnMut() -> bool,
+        mut f: impl FnMut(WatchEvent) -> ControlFlow<()>,
+    ) {
+        while let Some(due) = self.next_due() {
+            self.clock.sleep_until(due);
+            if !alive() {
+                return;
+            }
+            for event in self.poll() {
+                if f(event).is_break() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn watcher() {
+    use crate::mock::{MockKey, MockSmc};
+    use crate::structs::SMC_ATTR_READ;
+
+    let smc = IOService::with_transport(MockSmc::sample());
+    smc.transport().set_privileged(true);
+    let set_temp = |temp: f32| {
+        let key = MockKey::new(b"flt ", SMC_ATTR_READ, &temp.to_le_bytes());
+        smc.transport().insert(*b"TB0T", key);
+    };
+    let clock = ManualClock::new();
+    let start = clock.now();
+    let second = Duration::from_secs(1);
+    let mut watcher = Watcher::new(&smc)
+        .with_clock(&clock)
+        .watch_with_deadband(b"TB0T", second, 0.5)
+        .watch(b"ACLC", 2 * second)
+        .watch(b"NOPE", 2 * second);
+
+    let events = watcher.poll();
+    assert!(matches!(
+        &events[..],
+        [WatchEvent::Failed { key, error: SmcError::KeyNotFound, .. }] if *key == SmcKey::new(b"NOPE")
+    ));
+    assert_eq!(watcher.next_due(), Some(start + second));
+
+    // below the deadband, and not due
+    set_temp(26.0);
+    assert_eq!(smc.write_key(b"ACLC", &[5]), Ok(()));
+    clock.advance(second);
+    assert!(watcher.poll().is_empty());
+
+    // the small changes add up, ACLC and NOPE are due
+    set_temp(26.4);
+    clock.advance(second);
+    let events = watcher.poll();
+    assert_eq!(events.len(), 3);
+    let WatchEvent::Changed { key, old, new, at } = &events[0] else {
+        panic!("TB0T changed");
+    };
+    assert_eq!(*key, SmcKey::new(b"TB0T"));
+    assert_eq!(old.valid_bytes(), 25.8f32.to_le_bytes());
+    assert_eq!(new.valid_bytes(), 26.4f32.to_le_bytes());
+    assert_eq!(*at, start + 2 * second);
+    let WatchEvent::Changed { key, old, new, .. } = &events[1] else {
+        panic!("ACLC changed");
+    };
+    assert_eq!(*key, SmcKey::new(b"ACLC"));
+    assert_eq!((old.bytes[0], new.bytes[0]), (1, 5));
+
+    // run sleeps on the clock until the next change
+    let mut watcher = watcher.watch(b"ACLC", second);
+    set_temp(30.0);
+    let mut changed = Vec::new();
+    watcher.run(|event| match event {
+        WatchEvent::Changed { key, at, .. } => {
+            changed.push((key, at));
+            ControlFlow::Break(())
+        }
+        WatchEvent::Failed { .. } => ControlFlow::Continue(()),
+    });
+    assert_eq!(clock.now(), start + 3 * second);
+    assert_eq!(changed, [(SmcKey::new(b"TB0T"), start + 3 * second)]);
+
+    // run_into stops once the receiver is dropped, even without events
+    let (tx, rx) = channel();
+    std::thread::scope(|s| {
+        s.spawn(|| watcher.run_into(tx));
+        set_temp(35.0);
+        let mut rx = rx;
+        assert!(rx.any(|e| matches!(e, WatchEvent::Changed { .. })));
+        drop(rx);
+    });
+}
+
+#[test]
+#[should_panic(expected = "watch interval must not be zero")]
+fn zero_interval() {
+    let smc = IOService::with_transport(crate::mock::MockSmc::sample());
+    let _ = Watcher::new(&smc).watch(b"TB0T", Duration::ZERO);
+}